@@ -0,0 +1,12 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        if std::env::var_os("PROTOC").is_none() {
+            let protoc =
+                protoc_bin_vendored::protoc_bin_path().expect("Vendored protoc is available");
+            std::env::set_var("PROTOC", protoc);
+        }
+        tonic_prost_build::compile_protos("proto/exchange.proto")
+            .expect("Protobuf schema compiles");
+    }
+}
@@ -0,0 +1,118 @@
+use crate::connector::{Connector, ConnectorError, ExchangeOutput, LatestOutput};
+use crate::currency::Currency;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use error_stack::{Result, ResultExt};
+use tokio::runtime::Runtime;
+
+/// Asynchronous version of [`Connector`], for services and concurrent requests.
+///
+/// ```
+/// use exchangeapp::{AsyncConnector, MockConnector};
+///
+/// let runtime = tokio::runtime::Runtime::new().unwrap();
+/// let currencies = runtime
+///     .block_on(AsyncConnector::list_currencies(&MockConnector::new()))
+///     .unwrap();
+/// assert_eq!(3, currencies.len());
+/// ```
+#[async_trait]
+#[allow(clippy::module_name_repetitions)]
+pub trait AsyncConnector: Send + Sync {
+    async fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError>;
+
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError>;
+
+    async fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError>;
+
+    async fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError>;
+}
+
+pub fn runtime() -> Result<Runtime, ConnectorError> {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .change_context(ConnectorError::SendingError)
+        .attach_printable("Error with starting async runtime")
+}
+
+/// Blocking [`Connector`] running an [`AsyncConnector`] on its own runtime.
+///
+/// Must not be used from inside another async runtime.
+pub struct BlockingConnector<C> {
+    connector: C,
+    runtime: Runtime,
+}
+
+impl<C: AsyncConnector> BlockingConnector<C> {
+    pub fn new(connector: C) -> Result<Self, ConnectorError> {
+        Ok(BlockingConnector {
+            connector,
+            runtime: runtime()?,
+        })
+    }
+}
+
+impl<C: AsyncConnector> Connector for BlockingConnector<C> {
+    fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        self.runtime
+            .block_on(self.connector.exchange(source, target, amount))
+    }
+
+    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        self.runtime.block_on(self.connector.list_currencies())
+    }
+
+    fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.runtime.block_on(self.connector.latest(base, target))
+    }
+
+    fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.runtime
+            .block_on(self.connector.historical(date, base, target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::mock_connector::MockConnector;
+
+    #[test]
+    fn blocking_exchange() {
+        let connector = BlockingConnector::new(MockConnector::new()).unwrap();
+        assert_eq!(
+            ExchangeOutput::new(&BigDecimal::from(40), &BigDecimal::from(4)),
+            Connector::exchange(&connector, "USD", "PLN", &BigDecimal::from(10)).unwrap()
+        );
+    }
+}
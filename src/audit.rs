@@ -0,0 +1,248 @@
+use crate::async_connector::AsyncConnector;
+use crate::connector::{ConnectorError, LatestOutput};
+use bigdecimal::{BigDecimal, Zero};
+use error_stack::Result;
+use futures::future::try_join_all;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+struct Leg {
+    quote: usize,
+    source: String,
+    rate: BigDecimal,
+    inverse: bool,
+}
+
+impl fmt::Display for Leg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.inverse {
+            write!(f, "1/{}", self.source)
+        } else {
+            write!(f, "{}", self.source)
+        }
+    }
+}
+
+/// Exchange rates collected from one or more connectors.
+///
+/// Every quote `base -> target` is stored together with its inverse, so cycles
+/// can be closed with rates fetched for a different base.
+#[derive(Default)]
+pub struct RateGraph {
+    quotes: usize,
+    legs: BTreeMap<String, BTreeMap<String, Vec<Leg>>>,
+}
+
+impl RateGraph {
+    pub fn new() -> Self {
+        RateGraph::default()
+    }
+
+    pub fn add_latest(&mut self, source: &str, base: &str, latest: &[LatestOutput]) {
+        for output in latest {
+            let target = output.get_currency().get_short_code();
+            let rate = output.get_rate();
+            if target == base || rate.is_zero() {
+                continue;
+            }
+            let quote = self.quotes;
+            self.quotes += 1;
+            self.push_leg(base, target, quote, source, rate.clone(), false);
+            self.push_leg(
+                target,
+                base,
+                quote,
+                source,
+                BigDecimal::from(1) / rate,
+                true,
+            );
+        }
+    }
+
+    fn push_leg(
+        &mut self,
+        from: &str,
+        to: &str,
+        quote: usize,
+        source: &str,
+        rate: BigDecimal,
+        inverse: bool,
+    ) {
+        self.legs
+            .entry(from.to_string())
+            .or_default()
+            .entry(to.to_string())
+            .or_default()
+            .push(Leg {
+                quote,
+                source: source.to_string(),
+                rate,
+                inverse,
+            });
+    }
+
+    fn legs(&self, from: &str, to: &str) -> &[Leg] {
+        self.legs
+            .get(from)
+            .and_then(|targets| targets.get(to))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Find two and three currency cycles whose product of rates differs from 1 by more than `threshold`.
+    ///
+    /// Each cycle is reported once, in one direction, sorted by deviation descending.
+    pub fn find_inconsistencies(&self, threshold: &BigDecimal) -> Vec<Inconsistency> {
+        let mut found = Vec::new();
+        for (a, from_a) in &self.legs {
+            for (b, legs_ab) in from_a.iter().filter(|(b, _)| *b > a) {
+                for leg_ab in legs_ab {
+                    for leg_ba in self.legs(b, a) {
+                        if leg_ab.quote < leg_ba.quote {
+                            found.push(Inconsistency::new(
+                                vec![a.clone(), b.clone(), a.clone()],
+                                &[leg_ab, leg_ba],
+                            ));
+                        }
+                    }
+                }
+                let Some(from_b) = self.legs.get(b) else {
+                    continue;
+                };
+                for (c, legs_bc) in from_b.iter().filter(|(c, _)| *c > b) {
+                    let legs_ca = self.legs(c, a);
+                    for leg_ab in legs_ab {
+                        for leg_bc in legs_bc {
+                            for leg_ca in legs_ca {
+                                found.push(Inconsistency::new(
+                                    vec![a.clone(), b.clone(), c.clone(), a.clone()],
+                                    &[leg_ab, leg_bc, leg_ca],
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        found.retain(|inconsistency| inconsistency.deviation() > *threshold);
+        found.sort_by_key(|inconsistency| std::cmp::Reverse(inconsistency.deviation()));
+        found
+    }
+}
+
+/// Fetch latest rates of every base from every named connector concurrently
+pub async fn collect_latest(
+    sources: &[(String, Arc<dyn AsyncConnector>)],
+    bases: &[String],
+) -> Result<RateGraph, ConnectorError> {
+    let requests = sources.iter().flat_map(|(source, connector)| {
+        bases.iter().map(move |base| async move {
+            let latest = connector.latest(base, None).await?;
+            Ok::<_, error_stack::Report<ConnectorError>>((source, base, latest))
+        })
+    });
+    let mut graph = RateGraph::new();
+    for (source, base, latest) in try_join_all(requests).await? {
+        graph.add_latest(source, base, &latest);
+    }
+    Ok(graph)
+}
+
+#[derive(Debug)]
+pub struct Inconsistency {
+    cycle: Vec<String>,
+    sources: Vec<String>,
+    product: BigDecimal,
+}
+
+impl Inconsistency {
+    fn new(cycle: Vec<String>, legs: &[&Leg]) -> Self {
+        let product = legs
+            .iter()
+            .fold(BigDecimal::from(1), |product, leg| product * &leg.rate);
+        let sources = legs.iter().map(ToString::to_string).collect();
+        Inconsistency {
+            cycle,
+            sources,
+            product,
+        }
+    }
+    pub fn deviation(&self) -> BigDecimal {
+        (&self.product - BigDecimal::from(1)).abs()
+    }
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, product:{} ({})",
+            self.cycle.join(" -> "),
+            self.product.round(8),
+            self.sources.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+    use std::str::FromStr;
+
+    fn latest(rates: &[(&str, &str)]) -> Vec<LatestOutput> {
+        rates
+            .iter()
+            .map(|(code, rate)| {
+                LatestOutput::new(
+                    Currency::new(code, None),
+                    BigDecimal::from_str(rate).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn consistent_rates() {
+        let mut graph = RateGraph::new();
+        graph.add_latest("mock", "USD", &latest(&[("PLN", "4"), ("GBP", "0.8")]));
+        graph.add_latest("mock", "GBP", &latest(&[("PLN", "5"), ("USD", "1.25")]));
+        assert!(graph
+            .find_inconsistencies(&BigDecimal::from_str("0.001").unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn inconsistent_triangle() {
+        let mut graph = RateGraph::new();
+        graph.add_latest("mock", "USD", &latest(&[("PLN", "4"), ("GBP", "0.8")]));
+        graph.add_latest("mock", "GBP", &latest(&[("PLN", "5.5")]));
+        let found = graph.find_inconsistencies(&BigDecimal::from_str("0.001").unwrap());
+        assert_eq!(1, found.len());
+        assert_eq!(
+            vec!["GBP", "PLN", "USD", "GBP"],
+            found[0]
+                .cycle
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(BigDecimal::from_str("0.1").unwrap(), found[0].deviation());
+    }
+
+    #[test]
+    fn sources_disagree() {
+        let mut graph = RateGraph::new();
+        graph.add_latest("mock", "USD", &latest(&[("PLN", "4")]));
+        graph.add_latest("currencybeacon", "USD", &latest(&[("PLN", "4.2")]));
+        let found = graph.find_inconsistencies(&BigDecimal::from_str("0.001").unwrap());
+        assert_eq!(1, found.len());
+        assert_eq!(
+            vec!["1/mock".to_string(), "currencybeacon".to_string()],
+            found[0].sources
+        );
+    }
+}
@@ -0,0 +1,265 @@
+use crate::async_connector::AsyncConnector;
+use crate::connector::ConnectorError;
+use crate::currency::CurrencyPair;
+use crate::utility::round_trimmed;
+use bigdecimal::{BigDecimal, ToPrimitive, Zero};
+use chrono::{Days, NaiveDate};
+use error_stack::Result;
+use futures::stream::{self, StreamExt};
+use std::fmt;
+use std::fmt::Formatter;
+
+const MAX_CONCURRENT_REQUESTS: usize = 8;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Spread at most `points` dates evenly between `from` and `to`, both included
+pub fn sample_dates(from: NaiveDate, to: NaiveDate, points: usize) -> Vec<NaiveDate> {
+    let days = usize::try_from((to - from).num_days()).unwrap_or_default();
+    if points < 2 || days == 0 {
+        return vec![from];
+    }
+    let mut dates = (0..points)
+        .map(|i| from + Days::new((i * days / (points - 1)) as u64))
+        .collect::<Vec<_>>();
+    dates.dedup();
+    dates
+}
+
+/// Fetch exchange rate of `pair` on every date, dates without rate are skipped
+pub async fn fetch_points(
+    connector: &dyn AsyncConnector,
+    pair: &CurrencyPair,
+    dates: Vec<NaiveDate>,
+) -> Result<Vec<(NaiveDate, BigDecimal)>, ConnectorError> {
+    let results = stream::iter(dates)
+        .map(|date| async move {
+            let target = Some(vec![pair.get_target().to_string()]);
+            (
+                date,
+                connector.historical(date, pair.get_base(), target).await,
+            )
+        })
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .collect::<Vec<_>>()
+        .await;
+    let mut points = Vec::new();
+    for (date, rates) in results {
+        match rates {
+            Ok(rates) => {
+                if let Some(latest) = rates
+                    .iter()
+                    .find(|latest| latest.get_currency().get_short_code() == pair.get_target())
+                {
+                    points.push((date, latest.get_rate().clone()));
+                }
+            }
+            Err(err) if matches!(err.current_context(), ConnectorError::InvalidInput(_)) => {
+                tracing::warn!("No exchange rate for {pair} on {date}");
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(points)
+}
+
+pub struct Chart {
+    pair: CurrencyPair,
+    points: Vec<(NaiveDate, BigDecimal)>,
+    height: usize,
+    sparkline: bool,
+}
+
+impl Chart {
+    pub fn new(
+        pair: CurrencyPair,
+        points: Vec<(NaiveDate, BigDecimal)>,
+        height: usize,
+        sparkline: bool,
+    ) -> Self {
+        Chart {
+            pair,
+            points,
+            height: height.max(2),
+            sparkline,
+        }
+    }
+
+    fn min(&self) -> Option<&(NaiveDate, BigDecimal)> {
+        self.points.iter().min_by(|a, b| a.1.cmp(&b.1))
+    }
+
+    fn max(&self) -> Option<&(NaiveDate, BigDecimal)> {
+        self.points.iter().max_by(|a, b| a.1.cmp(&b.1))
+    }
+
+    fn average(&self) -> BigDecimal {
+        let sum = self
+            .points
+            .iter()
+            .fold(BigDecimal::zero(), |sum, (_, rate)| sum + rate);
+        sum / BigDecimal::from(self.points.len() as u64)
+    }
+
+    /// Percentage change between the first and the last point
+    fn change(&self) -> BigDecimal {
+        match (self.points.first(), self.points.last()) {
+            (Some((_, first)), Some((_, last))) if !first.is_zero() => {
+                (last - first) * BigDecimal::from(100) / first
+            }
+            _ => BigDecimal::zero(),
+        }
+    }
+
+    /// Position of every point scaled to `0..levels`
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn levels(&self, levels: usize) -> Vec<usize> {
+        let values = self
+            .points
+            .iter()
+            .map(|(_, rate)| rate.to_f64().unwrap_or_default())
+            .collect::<Vec<_>>();
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let top = (levels - 1) as f64;
+        values
+            .iter()
+            .map(|value| {
+                if max > min {
+                    ((value - min) / (max - min) * top).round() as usize
+                } else {
+                    levels / 2
+                }
+            })
+            .collect()
+    }
+
+    pub fn sparkline(&self) -> String {
+        self.levels(SPARKS.len())
+            .into_iter()
+            .map(|level| SPARKS[level])
+            .collect()
+    }
+
+    fn write_lines(
+        &self,
+        f: &mut Formatter<'_>,
+        min: &BigDecimal,
+        max: &BigDecimal,
+    ) -> fmt::Result {
+        let levels = self.levels(self.height);
+        let labels = (0..self.height)
+            .map(|row| {
+                let label = min
+                    + (max - min) * BigDecimal::from(row as u64)
+                        / BigDecimal::from((self.height - 1) as u64);
+                round_trimmed(&label, 4).to_string()
+            })
+            .collect::<Vec<_>>();
+        let label_width = labels.iter().map(String::len).max().unwrap_or_default();
+        for row in (0..self.height).rev() {
+            let line = levels
+                .iter()
+                .map(|&level| if level == row { '*' } else { ' ' })
+                .collect::<String>();
+            writeln!(f, "{:>label_width$} |{line}", labels[row])?;
+        }
+        writeln!(f, "{:>label_width$} +{}", "", "-".repeat(levels.len()))
+    }
+}
+
+impl fmt::Display for Chart {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (
+            Some((first_date, _)),
+            Some((last_date, _)),
+            Some((min_date, min)),
+            Some((max_date, max)),
+        ) = (
+            self.points.first(),
+            self.points.last(),
+            self.min(),
+            self.max(),
+        )
+        else {
+            return write!(f, "{}: no exchange rates", self.pair);
+        };
+        writeln!(f, "{} {first_date} - {last_date}", self.pair)?;
+        if self.sparkline {
+            writeln!(f, "{}", self.sparkline())?;
+        } else {
+            self.write_lines(f, min, max)?;
+        }
+        write!(
+            f,
+            "min: {min} ({min_date}), max: {max} ({max_date}), average: {}, change: {}%",
+            round_trimmed(&self.average(), 6),
+            round_trimmed(&self.change(), 2)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, day).unwrap()
+    }
+
+    fn chart(rates: &[i32]) -> Chart {
+        let points = rates
+            .iter()
+            .zip(1..)
+            .map(|(&rate, day)| (date(day), BigDecimal::from(rate)))
+            .collect();
+        Chart::new(CurrencyPair::from_str("USD/PLN").unwrap(), points, 3, false)
+    }
+
+    #[test]
+    fn every_day_sampled() {
+        assert_eq!(
+            vec![date(1), date(2), date(3)],
+            sample_dates(date(1), date(3), 10)
+        );
+    }
+
+    #[test]
+    fn dates_spread_evenly() {
+        assert_eq!(
+            vec![date(1), date(11), date(21), date(31)],
+            sample_dates(date(1), date(31), 4)
+        );
+    }
+
+    #[test]
+    fn sparkline() {
+        assert_eq!("▁▅█▁", chart(&[1, 5, 8, 1]).sparkline());
+        assert_eq!("▅▅", chart(&[4, 4]).sparkline());
+    }
+
+    #[test]
+    fn statistics() {
+        let chart = chart(&[4, 5, 3, 5]);
+        assert_eq!(BigDecimal::from_str("4.25").unwrap(), chart.average());
+        assert_eq!(BigDecimal::from(25), chart.change());
+        assert_eq!(Some(&(date(3), BigDecimal::from(3))), chart.min());
+    }
+
+    #[test]
+    fn line_chart() {
+        let expected = [
+            "USD/PLN 2026-01-01 - 2026-01-03",
+            "5 | * ",
+            "4 |*  ",
+            "3 |  *",
+            "  +---",
+            "min: 3 (2026-01-03), max: 5 (2026-01-02), average: 4, change: -25%",
+        ];
+        assert_eq!(expected.join("\n"), format!("{}", chart(&[4, 5, 3])));
+    }
+}
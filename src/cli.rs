@@ -0,0 +1,215 @@
+//! Command line interface of the `exchangeapp` binary
+mod handlers;
+
+use crate::metrics;
+use crate::telemetry::{self, LogFormat};
+use crate::utility::{get_connector, get_metrics_addr, set_apikey, set_connector, show_connectors};
+use crate::{create_connector, Connector, ConnectorEnum, ConnectorError};
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, Subcommand};
+use dotenv::dotenv;
+use error_stack::{Report, Result};
+use handlers::{
+    handle_audit_rates, handle_chart, handle_convert_file, handle_exchange, handle_historical,
+    handle_latest, handle_list_currencies, handle_portfolio, handle_stdio_rpc, handle_usage,
+    handle_watch, AuditRatesArgs, ChartArgs, ConvertFileArgs, ExchangeArgs, HistoricalArgs,
+    LatestArgs, PortfolioArgs, PortfolioOutput, WatchArgs,
+};
+#[cfg(feature = "local-history")]
+use handlers::{handle_record, RecordArgs};
+#[cfg(feature = "secrets")]
+use handlers::{handle_secrets, SecretsArgs};
+#[cfg(feature = "grpc")]
+use handlers::{handle_serve_grpc, ServeGrpcArgs};
+#[cfg(feature = "websocket")]
+use handlers::{handle_serve_ws, ServeWsArgs};
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command( about, long_about = None, arg_required_else_help(true))]
+struct Cli {
+    ///Show all available connectors
+    #[arg(long, exclusive = true)]
+    show_connectors: bool,
+
+    ///Set current connector
+    #[arg(long)]
+    set_connector: Option<ConnectorEnum>,
+
+    ///Set api key for chosen connector
+    #[arg(long)]
+    set_apikey: Option<String>,
+
+    ///More logs, -v for info and -vv for debug, `RUST_LOG` takes precedence
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    ///Format of logs written to stderr
+    #[arg(long, value_enum, default_value_t, global = true)]
+    log_format: LogFormat,
+
+    ///Serve Prometheus metrics on `http://<METRICS_ADDR>/metrics` while the command runs
+    #[arg(long, global = true)]
+    metrics_addr: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Exchange currency to another
+    Exchange(ExchangeArgs),
+    /// List all available currencies
+    ListCurrencies,
+    /// List currencies with exchange rate
+    Latest(LatestArgs),
+    /// List currencies with exchange rate on given date
+    Historical(HistoricalArgs),
+    /// Store latest exchange rates in local history database
+    #[cfg(feature = "local-history")]
+    Record(RecordArgs),
+    /// Draw exchange rate of a currency pair over time
+    Chart(ChartArgs),
+    /// Convert amounts in csv or json file to another currency
+    ConvertFile(ConvertFileArgs),
+    /// Value portfolio of holdings in different currencies
+    Portfolio(PortfolioArgs),
+    /// Report exchange rate cycles whose product differs from 1
+    AuditRates(AuditRatesArgs),
+    /// Poll exchange rate of a currency pair and notify about changes
+    Watch(WatchArgs),
+    /// Show requests made by connectors today and this month
+    Usage,
+    /// Serve exchange rates of the connector over gRPC
+    #[cfg(feature = "grpc")]
+    ServeGrpc(ServeGrpcArgs),
+    /// Push changed rates of subscribed currency pairs over WebSocket
+    #[cfg(feature = "websocket")]
+    ServeWs(ServeWsArgs),
+    /// Answer JSON-RPC 2.0 requests on stdin with responses on stdout, e.g. as a tool of an agent
+    StdioRpc,
+    /// Manage api keys in the encrypted secrets store
+    #[cfg(feature = "secrets")]
+    Secrets(SecretsArgs),
+}
+
+/// Parse arguments and run the command, the entry point of the binary
+pub fn main() {
+    dotenv().ok();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    telemetry::init(cli.log_format, cli.verbose);
+    let command = matches.subcommand_name().unwrap_or_default();
+    let _command = tracing::info_span!("command", command).entered();
+    if let Some(addr) = cli.metrics_addr.clone().or_else(get_metrics_addr) {
+        if let Err(err) = metrics::serve(&addr) {
+            report(command, &err);
+            std::process::exit(1);
+        }
+    }
+    let start = Instant::now();
+    run(&cli, command);
+    metrics::observe_command(command, start.elapsed());
+}
+
+/// Log error of `command` and count it in metrics
+fn report(command: &str, err: &Report<ConnectorError>) {
+    tracing::error!("\n{err:?}");
+    metrics::command_failed(command, err);
+}
+
+fn run(cli: &Cli, command: &str) {
+    if cli.show_connectors {
+        show_connectors();
+    }
+    if let Some(connector) = cli.set_connector {
+        set_connector(connector);
+    }
+    let enum_connector = get_connector();
+    if let Some(apikey) = &cli.set_apikey {
+        set_apikey(apikey, enum_connector);
+    }
+
+    if let Some(result) = cli.command.as_ref().and_then(dispatch_without_connector) {
+        if let Err(err) = result {
+            report(command, &err);
+        }
+        return;
+    }
+
+    let connector = match create_connector(enum_connector) {
+        Ok(connector) => connector,
+        Err(err) => {
+            report(command, &err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(args) = &cli.command {
+        if let Err(err) = dispatch(args, connector.as_ref()) {
+            report(command, &err);
+        }
+    }
+}
+
+fn print_lines<T: std::fmt::Display>(lines: &[T]) {
+    for line in lines {
+        println!("{line}");
+    }
+}
+
+/// Run subcommand which builds its own connectors or needs none, `None` for other subcommands
+fn dispatch_without_connector(command: &Commands) -> Option<Result<(), ConnectorError>> {
+    let result = match command {
+        Commands::AuditRates(args) => handle_audit_rates(args).map(|inconsistencies| {
+            if inconsistencies.is_empty() {
+                println!("No inconsistent rates found");
+            }
+            print_lines(&inconsistencies);
+        }),
+        Commands::Chart(args) => handle_chart(args).map(|chart| println!("{chart}")),
+        #[cfg(feature = "secrets")]
+        Commands::Secrets(args) => handle_secrets(args).map(|lines| print_lines(&lines)),
+        _ => return None,
+    };
+    Some(result)
+}
+
+/// Run subcommand with the connector of the app and print its output
+fn dispatch(command: &Commands, connector: &dyn Connector) -> Result<(), ConnectorError> {
+    match command {
+        Commands::Exchange(args) => println!("{}", handle_exchange(args, connector)?),
+        Commands::ListCurrencies => print_lines(&handle_list_currencies(connector)?),
+        Commands::Latest(args) => print_lines(&handle_latest(args, connector)?),
+        Commands::Historical(args) => print_lines(&handle_historical(args, connector)?),
+        #[cfg(feature = "local-history")]
+        Commands::Record(args) => {
+            println!(
+                "Recorded {} exchange rates",
+                handle_record(args, connector)?
+            );
+        }
+        Commands::ConvertFile(args) => println!("{}", handle_convert_file(args, connector)?),
+        Commands::Portfolio(args) => match handle_portfolio(args, connector)? {
+            PortfolioOutput::Value(valuation) => println!("{valuation}"),
+            PortfolioOutput::Comparison(comparison) => println!("{comparison}"),
+        },
+        Commands::Watch(args) => handle_watch(args, connector)?,
+        #[cfg(feature = "grpc")]
+        Commands::ServeGrpc(args) => handle_serve_grpc(args, connector)?,
+        #[cfg(feature = "websocket")]
+        Commands::ServeWs(args) => handle_serve_ws(args, connector)?,
+        Commands::StdioRpc => handle_stdio_rpc(connector)?,
+        Commands::Chart(_) | Commands::AuditRates(_) => {}
+        #[cfg(feature = "secrets")]
+        Commands::Secrets(_) => {}
+        Commands::Usage => {
+            let usage = handle_usage()?;
+            if usage.is_empty() {
+                println!("No requests made");
+            }
+            print_lines(&usage);
+        }
+    }
+    Ok(())
+}
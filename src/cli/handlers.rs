@@ -0,0 +1,459 @@
+use crate::async_connector::runtime;
+use crate::audit::{collect_latest, Inconsistency};
+use crate::chart::{fetch_points, sample_dates, Chart};
+use crate::connector::{
+    Connector, ConnectorBuilder, ConnectorEnum, ConnectorError, ExchangeOutput, LatestOutput,
+};
+use crate::convert_file::{convert_file, Columns, ConvertSummary, RateCache};
+use crate::currency::{Currency, CurrencyPair};
+#[cfg(feature = "local-history")]
+use crate::history::HistoryStore;
+use crate::portfolio::{Comparison, Portfolio, Valuation};
+#[cfg(feature = "secrets")]
+use crate::secrets::SecretsStore;
+use crate::usage::{usage_report, Usage};
+use crate::utility::{
+    connector_name, get_connector, get_portfolio_path, get_usage_path, parse_duration,
+};
+#[cfg(feature = "local-history")]
+use crate::utility::{get_history_db_path, get_record_bases};
+use crate::watch::{watch, Notifier, Watcher};
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, Utc};
+use clap::{Args, Subcommand};
+use error_stack::{Report, Result};
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Args, Debug)]
+pub struct ExchangeArgs {
+    /// Source currency code
+    #[arg(short, long)]
+    source: String,
+    /// Target currency code
+    #[arg(short, long)]
+    target: String,
+    /// Amount to be converted
+    #[arg(value_parser = clap::value_parser!(BigDecimal))]
+    amount: BigDecimal,
+}
+
+#[derive(Args, Debug)]
+pub struct LatestArgs {
+    /// Base currency code
+    #[arg(short, long)]
+    base: String,
+    /// Target currency code
+    #[arg(short, long)]
+    target: Option<Vec<String>>,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoricalArgs {
+    /// Date of exchange rates, e.g. 2026-01-31
+    #[arg(short, long)]
+    date: NaiveDate,
+    /// Base currency code
+    #[arg(short, long)]
+    base: String,
+    /// Target currency code
+    #[arg(short, long)]
+    target: Option<Vec<String>>,
+}
+
+#[cfg(feature = "local-history")]
+#[derive(Args, Debug)]
+pub struct RecordArgs {
+    /// Base currency codes to record, defaults to `EAPP_RECORD_BASES`
+    #[arg(short, long)]
+    base: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct ChartArgs {
+    /// Currency pair, e.g. USD/PLN
+    pair: CurrencyPair,
+    /// First date of the chart
+    #[arg(long)]
+    from: NaiveDate,
+    /// Last date of the chart, defaults to today
+    #[arg(long)]
+    to: Option<NaiveDate>,
+    /// Maximum number of dates to fetch exchange rates for
+    #[arg(long, default_value_t = 60)]
+    points: usize,
+    /// Chart height in lines
+    #[arg(long, default_value_t = 10)]
+    height: usize,
+    /// Print a one line sparkline instead of the chart
+    #[arg(long)]
+    sparkline: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ConvertFileArgs {
+    /// Input file, csv, json array or json lines
+    input: PathBuf,
+    /// Output file, defaults to input file name with .converted suffix
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Column with amount to be converted
+    #[arg(long)]
+    amount_col: String,
+    /// Column with currency code of the amount
+    #[arg(long)]
+    currency_col: String,
+    /// Column with date of the amount, rows without it use latest exchange rate
+    #[arg(long)]
+    date_col: Option<String>,
+    /// Target currency code
+    #[arg(long)]
+    to: String,
+}
+
+#[derive(Args, Debug)]
+pub struct PortfolioArgs {
+    /// Portfolio file, toml or json, defaults to `EAPP_PORTFOLIO` or portfolio.toml
+    #[arg(short, long)]
+    file: Option<PathBuf>,
+    #[command(subcommand)]
+    command: PortfolioCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PortfolioCommands {
+    /// Value every holding and the whole portfolio in one currency
+    Value(PortfolioValueArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct PortfolioValueArgs {
+    /// Currency code to value the portfolio in
+    #[arg(long = "in")]
+    currency: String,
+    /// Value the portfolio on this date instead of latest exchange rates
+    #[arg(short, long)]
+    date: Option<NaiveDate>,
+    /// Show change of value since this date
+    #[arg(short, long)]
+    compare: Option<NaiveDate>,
+}
+
+#[derive(Args, Debug)]
+pub struct AuditRatesArgs {
+    /// Base currency codes to fetch latest rates for
+    #[arg(short, long, required = true)]
+    base: Vec<String>,
+    /// Other connectors to compare with the current one
+    #[arg(short, long)]
+    connector: Vec<ConnectorEnum>,
+    /// Maximum allowed deviation of a cycle product from 1
+    #[arg(long, default_value = "0.001", value_parser = clap::value_parser!(BigDecimal))]
+    threshold: BigDecimal,
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Currency pair to watch, e.g. USD/PLN
+    #[arg(short, long)]
+    pair: CurrencyPair,
+    /// Notify when the rate rises above this value
+    #[arg(long, value_parser = clap::value_parser!(BigDecimal))]
+    above: Option<BigDecimal>,
+    /// Notify when the rate falls below this value
+    #[arg(long, value_parser = clap::value_parser!(BigDecimal))]
+    below: Option<BigDecimal>,
+    /// Notify when the rate moves by more than this percent
+    #[arg(long, value_parser = clap::value_parser!(BigDecimal))]
+    change: Option<BigDecimal>,
+    /// Polling interval, e.g. 30s, 5m or 1h
+    #[arg(short, long, default_value = "5m", value_parser = parse_duration)]
+    interval: Duration,
+    /// Shell command to run on every event, event is passed in `EAPP_EVENT` variables
+    #[arg(long)]
+    exec: Option<String>,
+    /// URL to POST every event to as JSON
+    #[arg(long)]
+    webhook: Option<String>,
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Args, Debug)]
+pub struct ServeGrpcArgs {
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:50051")]
+    addr: String,
+}
+
+#[cfg(feature = "websocket")]
+#[derive(Args, Debug)]
+pub struct ServeWsArgs {
+    /// Address to listen on
+    #[arg(short, long, default_value = "127.0.0.1:9001")]
+    addr: String,
+    /// Polling interval of subscribed pairs, e.g. 30s, 5m or 1h
+    #[arg(short, long, default_value = "1m", value_parser = parse_duration)]
+    interval: Duration,
+}
+
+#[cfg(feature = "secrets")]
+#[derive(Args, Debug)]
+pub struct SecretsArgs {
+    #[command(subcommand)]
+    command: SecretsCommands,
+}
+
+#[cfg(feature = "secrets")]
+#[derive(Subcommand, Debug)]
+pub enum SecretsCommands {
+    /// Store api key of a connector, the key is prompted for
+    Set(SecretsSetArgs),
+    /// List connectors with stored api keys
+    List,
+    /// Remove stored api key of a connector
+    Remove(SecretsRemoveArgs),
+}
+
+#[cfg(feature = "secrets")]
+#[derive(Args, Debug)]
+pub struct SecretsSetArgs {
+    /// Connector the api key belongs to
+    connector: ConnectorEnum,
+    /// Read the api key from the first line of stdin instead of prompting
+    #[arg(long)]
+    stdin: bool,
+}
+
+#[cfg(feature = "secrets")]
+#[derive(Args, Debug)]
+pub struct SecretsRemoveArgs {
+    /// Connector the api key belongs to
+    connector: ConnectorEnum,
+}
+
+pub fn handle_exchange(
+    args: &ExchangeArgs,
+    connector: &dyn Connector,
+) -> Result<ExchangeOutput, ConnectorError> {
+    connector.exchange(&args.source, &args.target, &args.amount)
+}
+
+pub fn handle_list_currencies(connector: &dyn Connector) -> Result<Vec<Currency>, ConnectorError> {
+    connector.list_currencies()
+}
+
+pub fn handle_latest(
+    args: &LatestArgs,
+    connector: &dyn Connector,
+) -> Result<Vec<LatestOutput>, ConnectorError> {
+    connector.latest(&args.base, args.target.clone())
+}
+
+pub fn handle_historical(
+    args: &HistoricalArgs,
+    connector: &dyn Connector,
+) -> Result<Vec<LatestOutput>, ConnectorError> {
+    connector.historical(args.date, &args.base, args.target.clone())
+}
+
+#[cfg(feature = "local-history")]
+pub fn handle_record(
+    args: &RecordArgs,
+    connector: &dyn Connector,
+) -> Result<usize, ConnectorError> {
+    let source = get_connector();
+    if source == ConnectorEnum::LocalHistory {
+        let msg = "Rates can not be recorded from the local history connector".to_string();
+        return Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg));
+    }
+    let bases = if args.base.is_empty() {
+        get_record_bases()
+    } else {
+        args.base.clone()
+    };
+    if bases.is_empty() {
+        let msg = "No base currency codes, pass --base or set EAPP_RECORD_BASES".to_string();
+        return Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg));
+    }
+    let mut store = HistoryStore::open(&get_history_db_path())?;
+    let recorded_at = Utc::now().naive_utc();
+    let mut recorded = 0;
+    for base in &bases {
+        let rates = connector.latest(base, None)?;
+        recorded += store.record(recorded_at, &connector_name(source), base, &rates)?;
+    }
+    Ok(recorded)
+}
+
+pub fn handle_chart(args: &ChartArgs) -> Result<Chart, ConnectorError> {
+    let to = args.to.unwrap_or_else(|| Utc::now().date_naive());
+    if args.from > to {
+        let msg = format!("Date {} is after {to}", args.from);
+        return Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg));
+    }
+    let connector = ConnectorBuilder::new(get_connector()).build_async()?;
+    let dates = sample_dates(args.from, to, args.points);
+    let points = runtime()?.block_on(fetch_points(connector.as_ref(), &args.pair, dates))?;
+    Ok(Chart::new(
+        args.pair.clone(),
+        points,
+        args.height,
+        args.sparkline,
+    ))
+}
+
+pub fn handle_convert_file(
+    args: &ConvertFileArgs,
+    connector: &dyn Connector,
+) -> Result<ConvertSummary, ConnectorError> {
+    let output = args.output.clone().unwrap_or_else(|| {
+        let mut name = args.input.file_stem().unwrap_or_default().to_os_string();
+        name.push(".converted");
+        if let Some(extension) = args.input.extension() {
+            name.push(".");
+            name.push(extension);
+        }
+        args.input.with_file_name(name)
+    });
+    let columns = Columns {
+        amount: args.amount_col.clone(),
+        currency: args.currency_col.clone(),
+        date: args.date_col.clone(),
+    };
+    let mut cache = RateCache::new(connector, &args.to);
+    convert_file(&args.input, &output, &columns, &mut cache)
+}
+
+pub enum PortfolioOutput {
+    Value(Valuation),
+    Comparison(Comparison),
+}
+
+pub fn handle_portfolio(
+    args: &PortfolioArgs,
+    connector: &dyn Connector,
+) -> Result<PortfolioOutput, ConnectorError> {
+    let portfolio = Portfolio::load(&args.file.clone().unwrap_or_else(get_portfolio_path))?;
+    match &args.command {
+        PortfolioCommands::Value(args) => {
+            let currency = args.currency.to_uppercase();
+            let value = |date: Option<NaiveDate>| {
+                let (label, rates) = match date {
+                    Some(date) => (
+                        date.to_string(),
+                        connector.historical(date, &currency, None)?,
+                    ),
+                    None => ("latest".to_string(), connector.latest(&currency, None)?),
+                };
+                portfolio.value(&currency, &label, &rates)
+            };
+            let valuation = value(args.date)?;
+            match args.compare {
+                Some(compare) => Ok(PortfolioOutput::Comparison(Comparison::new(
+                    value(Some(compare))?,
+                    valuation,
+                ))),
+                None => Ok(PortfolioOutput::Value(valuation)),
+            }
+        }
+    }
+}
+
+pub fn handle_audit_rates(args: &AuditRatesArgs) -> Result<Vec<Inconsistency>, ConnectorError> {
+    let current = get_connector();
+    let mut sources = Vec::new();
+    for connector in std::iter::once(current).chain(args.connector.iter().copied()) {
+        if sources
+            .iter()
+            .all(|(name, _)| *name != connector_name(connector))
+        {
+            let async_connector = ConnectorBuilder::new(connector).build_async()?;
+            sources.push((connector_name(connector), async_connector));
+        }
+    }
+    let graph = runtime()?.block_on(collect_latest(&sources, &args.base))?;
+    Ok(graph.find_inconsistencies(&args.threshold))
+}
+
+pub fn handle_watch(args: &WatchArgs, connector: &dyn Connector) -> Result<(), ConnectorError> {
+    let watcher = Watcher::new(args.above.clone(), args.below.clone(), args.change.clone());
+    let notifier = Notifier::new(args.exec.clone(), args.webhook.clone());
+    watch(connector, &args.pair, args.interval, watcher, &notifier)
+}
+
+#[cfg(feature = "grpc")]
+pub fn handle_serve_grpc(
+    args: &ServeGrpcArgs,
+    connector: &dyn Connector,
+) -> Result<(), ConnectorError> {
+    crate::grpc::serve(connector, &args.addr)
+}
+
+pub fn handle_stdio_rpc(connector: &dyn Connector) -> Result<(), ConnectorError> {
+    crate::rpc::serve(connector, io::stdin().lock(), io::stdout().lock())
+}
+
+#[cfg(feature = "websocket")]
+pub fn handle_serve_ws(
+    args: &ServeWsArgs,
+    connector: &dyn Connector,
+) -> Result<(), ConnectorError> {
+    crate::ws::serve(connector, &args.addr, args.interval)
+}
+
+pub fn handle_usage() -> Result<Vec<Usage>, ConnectorError> {
+    usage_report(&get_usage_path(), Utc::now().date_naive())
+}
+
+/// Lines to print after running a `secrets` subcommand
+#[cfg(feature = "secrets")]
+pub fn handle_secrets(args: &SecretsArgs) -> Result<Vec<String>, ConnectorError> {
+    let mut store = SecretsStore::new()?;
+    match &args.command {
+        SecretsCommands::Set(args) => {
+            let name = connector_name(args.connector);
+            let secret = read_secret(&name, args.stdin)?;
+            store.set(&name, &secret);
+            store.save()?;
+            Ok(vec![format!("Stored api key of {name}")])
+        }
+        SecretsCommands::List => {
+            let names = store.names().map(str::to_string).collect::<Vec<_>>();
+            if names.is_empty() {
+                return Ok(vec!["No api keys stored".to_string()]);
+            }
+            Ok(names)
+        }
+        SecretsCommands::Remove(args) => {
+            let name = connector_name(args.connector);
+            if !store.remove(&name) {
+                let msg = format!("No api key of {name} stored");
+                return Err(
+                    Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+                );
+            }
+            store.save()?;
+            Ok(vec![format!("Removed api key of {name}")])
+        }
+    }
+}
+
+#[cfg(feature = "secrets")]
+fn read_secret(name: &str, stdin: bool) -> Result<String, ConnectorError> {
+    let secret = if stdin {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line).map(|_| line)
+    } else {
+        rpassword::prompt_password(format!("Api key of {name}: "))
+    }
+    .map_err(|err| Report::new(ConnectorError::InvalidInput(err.to_string())))?;
+    let secret = secret.trim();
+    if secret.is_empty() {
+        return Err(
+            Report::new(ConnectorError::InvalidInput("Api key is empty".to_string()))
+                .attach_printable("Api key is empty"),
+        );
+    }
+    Ok(secret.to_string())
+}
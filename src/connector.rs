@@ -1,11 +1,32 @@
+use crate::async_connector::AsyncConnector;
+#[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+use crate::async_connector::BlockingConnector;
 pub use crate::connector_output::{ExchangeOutput, LatestOutput};
+#[cfg(feature = "currencybeacon")]
+use crate::connectors::api_keys::ApiKeys;
+#[cfg(feature = "currencybeacon")]
 use crate::connectors::currencybeacon_connector::CurrencybeaconConnector;
+#[cfg(feature = "file")]
+use crate::connectors::file_connector::FileConnector;
+#[cfg(feature = "generic-rest")]
+use crate::connectors::generic_rest_connector::GenericRestConnector;
+#[cfg(feature = "currencybeacon")]
+use crate::connectors::http::HttpClient;
+#[cfg(feature = "local-history")]
+use crate::connectors::local_history_connector::LocalHistoryConnector;
 use crate::connectors::mock_connector::MockConnector;
 use crate::currency::Currency;
+use crate::telemetry::TracedConnector;
+#[cfg(feature = "generic-rest")]
+use crate::utility::get_rest_config_path;
+use crate::utility::{connector_name, get_mock_config_path};
 use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
 use clap::ValueEnum;
-use error_stack::{Context, Result};
+use error_stack::{AttachmentKind, Context, FrameKind, Report, Result};
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
 use strum_macros::{Display, EnumIter, EnumString};
 
 #[derive(
@@ -21,24 +42,205 @@ use strum_macros::{Display, EnumIter, EnumString};
     strum_macros::EnumProperty,
     EnumString,
     Debug,
+    Default,
 )]
 #[allow(clippy::module_name_repetitions)]
 pub enum ConnectorEnum {
+    #[default]
     #[strum(props(API_KEY_ENV = ""))]
     Mock,
+    #[cfg(feature = "currencybeacon")]
     #[strum(props(API_KEY_ENV = "EAPP_CURRENCYBEACON"))]
     Currencybeacon,
+    #[cfg(feature = "local-history")]
+    #[strum(props(API_KEY_ENV = ""))]
+    LocalHistory,
+    #[cfg(feature = "generic-rest")]
+    #[strum(props(API_KEY_ENV = "EAPP_GENERIC_REST"))]
+    GenericRest,
+    #[cfg(feature = "file")]
+    #[strum(props(API_KEY_ENV = ""))]
+    File,
 }
 
+impl ConnectorEnum {
+    /// Connectors left out of this build, named after the cargo feature which enables them
+    pub const MISSING: &'static [&'static str] = &[
+        #[cfg(not(feature = "currencybeacon"))]
+        "currencybeacon",
+        #[cfg(not(feature = "local-history"))]
+        "local-history",
+        #[cfg(not(feature = "generic-rest"))]
+        "generic-rest",
+        #[cfg(not(feature = "file"))]
+        "file",
+    ];
+}
+
+/// Create connector configured from environment variables
 #[allow(clippy::module_name_repetitions)]
 pub fn create_connector(connector: ConnectorEnum) -> Result<Box<dyn Connector>, ConnectorError> {
-    match connector {
-        ConnectorEnum::Currencybeacon => Ok(Box::new(CurrencybeaconConnector::new()?)),
-        ConnectorEnum::Mock => Ok(Box::new(MockConnector::new())),
+    ConnectorBuilder::new(connector).build()
+}
+
+/// Configuration of a connector, settings which are not given are read from environment variables.
+///
+/// ```
+/// # #[cfg(feature = "currencybeacon")] {
+/// use exchangeapp::{ConnectorBuilder, ConnectorEnum};
+///
+/// let connector = ConnectorBuilder::new(ConnectorEnum::Currencybeacon)
+///     .api_key("API_KEY")
+///     .build();
+/// assert!(connector.is_ok());
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ConnectorBuilder {
+    connector: ConnectorEnum,
+    mock_config: Option<PathBuf>,
+    #[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+    api_key: Option<String>,
+    #[cfg(feature = "local-history")]
+    history_db: Option<PathBuf>,
+    #[cfg(feature = "generic-rest")]
+    rest_config: Option<PathBuf>,
+    #[cfg(feature = "file")]
+    rates_file: Option<PathBuf>,
+}
+
+impl ConnectorBuilder {
+    pub fn new(connector: ConnectorEnum) -> Self {
+        ConnectorBuilder {
+            connector,
+            ..ConnectorBuilder::default()
+        }
+    }
+    /// Config of [`ConnectorEnum::Mock`] used instead of `EAPP_MOCK_CONFIG`
+    #[must_use]
+    pub fn mock_config(mut self, path: impl Into<PathBuf>) -> Self {
+        self.mock_config = Some(path.into());
+        self
+    }
+    /// Api key used instead of the one from connector environment variable
+    #[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+    #[must_use]
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+    /// Database of [`ConnectorEnum::LocalHistory`] used instead of `EAPP_HISTORY_DB`
+    #[cfg(feature = "local-history")]
+    #[must_use]
+    pub fn history_db(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_db = Some(path.into());
+        self
+    }
+    /// Config of [`ConnectorEnum::GenericRest`] used instead of `EAPP_REST_CONFIG`
+    #[cfg(feature = "generic-rest")]
+    #[must_use]
+    pub fn rest_config(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rest_config = Some(path.into());
+        self
+    }
+    /// Rates file of [`ConnectorEnum::File`] used instead of `EAPP_RATES_FILE`
+    #[cfg(feature = "file")]
+    #[must_use]
+    pub fn rates_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rates_file = Some(path.into());
+        self
+    }
+    fn mock(&self) -> Result<MockConnector, ConnectorError> {
+        match self.mock_config.clone().or_else(get_mock_config_path) {
+            Some(path) => MockConnector::open(&path),
+            None => Ok(MockConnector::new()),
+        }
+    }
+    #[cfg(feature = "currencybeacon")]
+    fn currencybeacon(&self) -> Result<CurrencybeaconConnector, ConnectorError> {
+        match &self.api_key {
+            Some(api_key) => Ok(CurrencybeaconConnector::with_keys(
+                ApiKeys::from_env(api_key)?,
+                HttpClient::new(ConnectorEnum::Currencybeacon)?,
+            )),
+            None => CurrencybeaconConnector::new(),
+        }
+    }
+    #[cfg(feature = "local-history")]
+    fn local_history(&self) -> Result<LocalHistoryConnector, ConnectorError> {
+        match &self.history_db {
+            Some(path) => LocalHistoryConnector::open(path),
+            None => LocalHistoryConnector::new(),
+        }
+    }
+    #[cfg(feature = "generic-rest")]
+    fn generic_rest(&self) -> Result<GenericRestConnector, ConnectorError> {
+        let path = self
+            .rest_config
+            .clone()
+            .unwrap_or_else(get_rest_config_path);
+        let api_key = match &self.api_key {
+            Some(api_key) => Some(api_key.clone()),
+            None => GenericRestConnector::env_api_key()?,
+        };
+        GenericRestConnector::open(&path, api_key)
+    }
+    #[cfg(feature = "file")]
+    fn file(&self) -> Result<FileConnector, ConnectorError> {
+        match &self.rates_file {
+            Some(path) => FileConnector::open(path),
+            None => FileConnector::new(),
+        }
+    }
+    /// Build connector, every call is traced in a `connector` span
+    pub fn build(self) -> Result<Box<dyn Connector>, ConnectorError> {
+        let name = connector_name(self.connector);
+        match self.connector {
+            #[cfg(feature = "currencybeacon")]
+            ConnectorEnum::Currencybeacon => Ok(Box::new(TracedConnector::new(
+                &name,
+                BlockingConnector::new(self.currencybeacon()?)?,
+            ))),
+            ConnectorEnum::Mock => Ok(Box::new(TracedConnector::new(&name, self.mock()?))),
+            #[cfg(feature = "local-history")]
+            ConnectorEnum::LocalHistory => {
+                Ok(Box::new(TracedConnector::new(&name, self.local_history()?)))
+            }
+            #[cfg(feature = "generic-rest")]
+            ConnectorEnum::GenericRest => Ok(Box::new(TracedConnector::new(
+                &name,
+                BlockingConnector::new(self.generic_rest()?)?,
+            ))),
+            #[cfg(feature = "file")]
+            ConnectorEnum::File => Ok(Box::new(TracedConnector::new(&name, self.file()?))),
+        }
+    }
+    /// Build connector for use inside an async runtime
+    pub fn build_async(self) -> Result<Arc<dyn AsyncConnector>, ConnectorError> {
+        let name = connector_name(self.connector);
+        match self.connector {
+            #[cfg(feature = "currencybeacon")]
+            ConnectorEnum::Currencybeacon => Ok(Arc::new(TracedConnector::new(
+                &name,
+                self.currencybeacon()?,
+            ))),
+            ConnectorEnum::Mock => Ok(Arc::new(TracedConnector::new(&name, self.mock()?))),
+            #[cfg(feature = "local-history")]
+            ConnectorEnum::LocalHistory => {
+                Ok(Arc::new(TracedConnector::new(&name, self.local_history()?)))
+            }
+            #[cfg(feature = "generic-rest")]
+            ConnectorEnum::GenericRest => {
+                Ok(Arc::new(TracedConnector::new(&name, self.generic_rest()?)))
+            }
+            #[cfg(feature = "file")]
+            ConnectorEnum::File => Ok(Arc::new(TracedConnector::new(&name, self.file()?))),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, strum_macros::IntoStaticStr)]
 #[allow(clippy::module_name_repetitions)]
 pub enum ConnectorError {
     InvalidInput(String),
@@ -47,6 +249,10 @@ pub enum ConnectorError {
     SendingError,
     JsonParsingError,
     ParseError,
+    StorageError,
+    FileError,
+    BudgetExceeded,
+    RateLimited,
 }
 
 impl fmt::Display for ConnectorError {
@@ -57,6 +263,65 @@ impl fmt::Display for ConnectorError {
 
 impl Context for ConnectorError {}
 
+/// Error reported by a provider api, attached below [`ConnectorError::ApiError`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum_macros::IntoStaticStr)]
+pub enum ApiError {
+    ServerError,
+    TooManyRequests,
+    AuthorizationError,
+    /// Currency code or other request parameter not supported by the api
+    InvalidCurrency,
+    /// Request not available in the current subscription plan
+    PlanRestriction,
+    /// Request allowance of the api key used up
+    QuotaExceeded,
+    SomethingElse,
+}
+impl fmt::Display for ApiError {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(match self {
+            ApiError::ServerError => "Api server error",
+            ApiError::TooManyRequests => "Too many requests to api",
+            ApiError::AuthorizationError => "Api key is missing or invalid",
+            ApiError::InvalidCurrency => "Api does not support requested currency",
+            ApiError::PlanRestriction => "Request is not available in api plan",
+            ApiError::QuotaExceeded => "Api request quota is used up",
+            ApiError::SomethingElse => "Error with api",
+        })
+    }
+}
+
+impl Context for ApiError {}
+
+/// Printable attachments of the report, innermost first, without locations and backtraces
+pub fn error_message(err: &Report<ConnectorError>) -> String {
+    let mut messages = err
+        .frames()
+        .filter_map(|frame| match frame.kind() {
+            FrameKind::Attachment(AttachmentKind::Printable(attachment)) => {
+                Some(attachment.to_string())
+            }
+            FrameKind::Context(_) => frame.downcast_ref::<ApiError>().map(ToString::to_string),
+            FrameKind::Attachment(_) => None,
+        })
+        .collect::<Vec<_>>();
+    messages.reverse();
+    if messages.is_empty() {
+        err.current_context().to_string()
+    } else {
+        messages.join(": ")
+    }
+}
+
+/// Source of currencies and exchange rates.
+///
+/// ```
+/// use exchangeapp::{Connector, MockConnector};
+///
+/// let connector = MockConnector::new();
+/// let latest = connector.latest("USD", Some(vec!["PLN".to_string()])).unwrap();
+/// assert_eq!("PLN", latest[0].get_currency().get_short_code());
+/// ```
 pub trait Connector {
     fn exchange(
         &self,
@@ -71,4 +336,11 @@ pub trait Connector {
         base: &str,
         target: Option<Vec<String>>,
     ) -> Result<Vec<LatestOutput>, ConnectorError>;
+
+    fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError>;
 }
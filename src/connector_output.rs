@@ -4,6 +4,15 @@ use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
 
+/// Result of an exchange, amount in target currency and rate used.
+///
+/// ```
+/// use bigdecimal::BigDecimal;
+/// use exchangeapp::ExchangeOutput;
+///
+/// let output = ExchangeOutput::new(&BigDecimal::from(40), &BigDecimal::from(4));
+/// assert_eq!("40, exchange rate:4", format!("{output}"));
+/// ```
 #[derive(Debug, PartialEq)]
 pub struct ExchangeOutput {
     value: BigDecimal,
@@ -18,6 +27,12 @@ impl ExchangeOutput {
             exchange_rate,
         }
     }
+    pub fn get_value(&self) -> &BigDecimal {
+        &self.value
+    }
+    pub fn get_rate(&self) -> &BigDecimal {
+        &self.exchange_rate
+    }
 }
 impl fmt::Display for ExchangeOutput {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -25,6 +40,16 @@ impl fmt::Display for ExchangeOutput {
     }
 }
 
+/// Exchange rate from a base currency to `currency`.
+///
+/// ```
+/// use bigdecimal::BigDecimal;
+/// use exchangeapp::{Currency, LatestOutput};
+///
+/// let latest = LatestOutput::new(Currency::new("PLN", None), BigDecimal::from(4));
+/// assert_eq!(&BigDecimal::from(4), latest.get_rate());
+/// assert_eq!("PLN, exchange rate:4", format!("{latest}"));
+/// ```
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct LatestOutput {
     currency: Currency,
@@ -41,6 +66,9 @@ impl LatestOutput {
     pub fn get_rate(&self) -> &BigDecimal {
         &self.exchange_rate
     }
+    pub fn get_currency(&self) -> &Currency {
+        &self.currency
+    }
 }
 impl fmt::Display for LatestOutput {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
@@ -0,0 +1,310 @@
+use crate::connector::{ApiError, ConnectorError};
+use crate::state_file::{self, write_atomic};
+use crate::utility::{get_api_keys_path, get_key_rotation};
+use chrono::{DateTime, Datelike, Months, TimeDelta, Utc};
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use strum_macros::{Display, EnumString};
+
+/// Order in which [`ApiKeys`] are tried
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Display, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum KeyRotation {
+    /// Use the first key which is not exhausted
+    #[default]
+    Failover,
+    /// Start every request with the next key
+    RoundRobin,
+}
+
+/// Api keys of a connector, e.g. `KEY_A,KEY_B` in `EAPP_CURRENCYBEACON`.
+///
+/// A key answered with [`ApiError::AuthorizationError`], [`ApiError::TooManyRequests`]
+/// or [`ApiError::QuotaExceeded`] is exhausted until its window resets and the next key is tried.
+/// Exhausted keys can be kept in a state file, which stores only fingerprints of the keys.
+pub struct ApiKeys {
+    keys: Vec<String>,
+    rotation: KeyRotation,
+    next: AtomicUsize,
+    exhausted: Mutex<HashMap<String, DateTime<Utc>>>,
+    state: Option<PathBuf>,
+}
+
+impl ApiKeys {
+    /// Keys separated by commas
+    pub fn new(keys: &str) -> Self {
+        ApiKeys {
+            keys: keys
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(str::to_string)
+                .collect(),
+            rotation: KeyRotation::default(),
+            next: AtomicUsize::new(0),
+            exhausted: Mutex::new(HashMap::new()),
+            state: None,
+        }
+    }
+    /// Keys with rotation from `EAPP_KEY_ROTATION` and state file from `EAPP_API_KEYS_FILE`
+    pub fn from_env(keys: &str) -> Result<Self, ConnectorError> {
+        let rotation = get_key_rotation();
+        let rotation = KeyRotation::from_str(&rotation)
+            .change_context_lazy(|| {
+                ConnectorError::InvalidInput(format!("Invalid key rotation {rotation}"))
+            })
+            .attach_printable("Expected failover or round-robin")?;
+        ApiKeys::new(keys)
+            .rotation(rotation)
+            .state_file(&get_api_keys_path())
+    }
+    #[must_use]
+    pub fn rotation(mut self, rotation: KeyRotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+    /// Keep exhausted keys in file at `path`, shared with other processes through an
+    /// advisory lock on a file next to it
+    pub fn state_file(mut self, path: &Path) -> Result<Self, ConnectorError> {
+        *self
+            .exhausted
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = load_state(path)?;
+        self.state = Some(path.to_path_buf());
+        Ok(self)
+    }
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+    pub fn key(&self, index: usize) -> &str {
+        &self.keys[index]
+    }
+    /// Indices of keys to try for the next request, exhausted keys are skipped
+    pub fn candidates(&self, now: DateTime<Utc>) -> Result<Vec<usize>, ConnectorError> {
+        if self.keys.is_empty() {
+            return Err(Report::new(ConnectorError::ApiKeyRequirements)
+                .attach_printable("This connector require api key"));
+        }
+        let exhausted = self
+            .exhausted
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let blocked_until = |index: usize| {
+            exhausted
+                .get(&fingerprint(&self.keys[index]))
+                .copied()
+                .filter(|until| *until > now)
+        };
+        let start = match self.rotation {
+            KeyRotation::Failover => 0,
+            KeyRotation::RoundRobin => self.next.fetch_add(1, Ordering::Relaxed),
+        };
+        let candidates = (0..self.keys.len())
+            .map(|i| (start + i) % self.keys.len())
+            .filter(|index| blocked_until(*index).is_none())
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            let until = (0..self.keys.len()).filter_map(blocked_until).min();
+            return Err(
+                Report::new(ConnectorError::ApiKeyRequirements).attach_printable(format!(
+                    "All {} api keys are exhausted, first one is available at {}",
+                    self.keys.len(),
+                    until.unwrap_or(now)
+                )),
+            );
+        }
+        Ok(candidates)
+    }
+    /// Mark key at `index` exhausted when `err` means it can't be used for now,
+    /// returns whether another key should be tried
+    pub async fn exhaust_on(
+        &self,
+        index: usize,
+        err: &Report<ConnectorError>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let Some(until) = exhausted_until(err, now) else {
+            return false;
+        };
+        tracing::warn!(
+            "Api key {} of {} is exhausted until {until}",
+            index + 1,
+            self.keys.len()
+        );
+        let exhausted = {
+            let mut exhausted = self
+                .exhausted
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+            exhausted.insert(fingerprint(&self.keys[index]), until);
+            exhausted.clone()
+        };
+        if let Some(path) = &self.state {
+            let state = path.clone();
+            match state_file::update(path, move || save_state(&state, &exhausted, now)).await {
+                Ok(merged) => self
+                    .exhausted
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .extend(merged),
+                Err(err) => tracing::warn!("{err:?}"),
+            }
+        }
+        true
+    }
+}
+
+/// End of the window in which a key answered with `err` can't be used
+fn exhausted_until(err: &Report<ConnectorError>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    match err.downcast_ref::<ApiError>()? {
+        ApiError::TooManyRequests => Some(now + TimeDelta::minutes(1)),
+        ApiError::AuthorizationError => Some(now + TimeDelta::days(1)),
+        ApiError::QuotaExceeded => Some(
+            now.date_naive()
+                .with_day(1)?
+                .checked_add_months(Months::new(1))?
+                .and_hms_opt(0, 0, 0)?
+                .and_utc(),
+        ),
+        _ => None,
+    }
+}
+
+/// FNV-1a hash of the key, so keys themselves are never written to the state file
+fn fingerprint(key: &str) -> String {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// State is a json object of exhaustion ends in RFC 3339 by key fingerprint
+fn load_state(path: &Path) -> Result<HashMap<String, DateTime<Utc>>, ConnectorError> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let text = fs::read_to_string(path)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+    let json = json::parse(&text)
+        .change_context(ConnectorError::JsonParsingError)
+        .attach_printable_lazy(|| format!("Error in api keys file {}", path.display()))?;
+    Ok(json
+        .entries()
+        .filter_map(|(key, until)| {
+            let until = DateTime::parse_from_rfc3339(until.as_str()?).ok()?;
+            Some((key.to_string(), until.with_timezone(&Utc)))
+        })
+        .collect())
+}
+
+/// Merge `exhausted` into the state file, forgetting keys which are usable again.
+/// The caller holds the lock of the state file, keys exhausted by other processes are returned.
+fn save_state(
+    path: &Path,
+    exhausted: &HashMap<String, DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> Result<HashMap<String, DateTime<Utc>>, ConnectorError> {
+    let mut state = load_state(path).unwrap_or_default();
+    for (key, until) in exhausted {
+        let latest = state.entry(key.clone()).or_insert(*until);
+        *latest = (*latest).max(*until);
+    }
+    state.retain(|_, until| *until > now);
+    let mut json = JsonValue::new_object();
+    for (key, until) in &state {
+        json[key.as_str()] = until.to_rfc3339().into();
+    }
+    write_atomic(path, &json.pretty(2))?;
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn api_error(error: ApiError) -> Report<ConnectorError> {
+        Report::new(error).change_context(ConnectorError::ApiError)
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-20T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[tokio::test]
+    async fn round_robin() {
+        let keys = ApiKeys::new("A, B,C").rotation(KeyRotation::RoundRobin);
+        assert_eq!(vec![0, 1, 2], keys.candidates(now()).unwrap());
+        assert_eq!(vec![1, 2, 0], keys.candidates(now()).unwrap());
+        assert!(
+            keys.exhaust_on(2, &api_error(ApiError::TooManyRequests), now())
+                .await
+        );
+        assert_eq!(vec![0, 1], keys.candidates(now()).unwrap());
+        assert_eq!(
+            vec![0, 1, 2],
+            keys.candidates(now() + TimeDelta::minutes(2)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn exhausted_until_quota_resets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api-keys.json");
+        let keys = ApiKeys::new("A,B").state_file(&path).unwrap();
+        assert!(
+            !keys
+                .exhaust_on(0, &api_error(ApiError::InvalidCurrency), now())
+                .await
+        );
+        assert!(
+            keys.exhaust_on(0, &api_error(ApiError::QuotaExceeded), now())
+                .await
+        );
+        assert!(
+            keys.exhaust_on(1, &api_error(ApiError::AuthorizationError), now())
+                .await
+        );
+        let state = fs::read_to_string(&path).unwrap();
+        let keys = ApiKeys::new("A,B").state_file(&path).unwrap();
+        assert!(!state.contains("\"A\""));
+        let err = keys.candidates(now()).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::ApiKeyRequirements
+        ));
+        assert_eq!(
+            vec![1],
+            keys.candidates(now() + TimeDelta::days(2)).unwrap()
+        );
+        let next_month = DateTime::parse_from_rfc3339("2024-06-01T00:00:00Z").unwrap();
+        assert_eq!(
+            vec![0, 1],
+            keys.candidates(next_month.with_timezone(&Utc)).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn processes_share_exhausted_keys() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api-keys-shared.json");
+        let first = ApiKeys::new("A,B,C").state_file(&path).unwrap();
+        let second = ApiKeys::new("A,B,C").state_file(&path).unwrap();
+        let err = api_error(ApiError::QuotaExceeded);
+        let (exhausted_a, exhausted_b) = futures::join!(
+            first.exhaust_on(0, &err, now()),
+            second.exhaust_on(1, &err, now()),
+        );
+        let third = ApiKeys::new("A,B,C").state_file(&path).unwrap();
+        assert!(exhausted_a && exhausted_b);
+        assert_eq!(vec![2], third.candidates(now()).unwrap());
+    }
+}
@@ -1,127 +1,324 @@
-use crate::connector::{Connector, ConnectorEnum, ConnectorError, ExchangeOutput, LatestOutput};
+use crate::async_connector::AsyncConnector;
+use crate::connector::{ApiError, ConnectorEnum, ConnectorError, ExchangeOutput, LatestOutput};
+use crate::connectors::api_keys::ApiKeys;
+use crate::connectors::http::HttpClient;
+use crate::connectors::response::{parse_currencies, parse_rates};
 use crate::currency::Currency;
-use crate::utility::API_KEY_ENV;
+use crate::redact::redact;
+use crate::utility::get_api_key;
+use async_trait::async_trait;
 use bigdecimal::BigDecimal;
-use error_stack::{Context, Report, Result, ResultExt};
+use chrono::{NaiveDate, Utc};
+use error_stack::{Report, Result};
 use json::JsonValue;
-use reqwest::StatusCode;
-use std::str::FromStr;
-use std::{env, fmt};
-use strum::EnumProperty;
 
 const BASE_URL: &str = "https://api.currencybeacon.com/v1/";
 pub struct CurrencybeaconConnector {
-    http_client: reqwest::blocking::Client,
-    api_key: String,
+    http_client: HttpClient,
+    api_keys: ApiKeys,
 }
 
 impl CurrencybeaconConnector {
     pub fn new() -> Result<Self, ConnectorError> {
         let connector = ConnectorEnum::Currencybeacon;
-        match env::var(connector.get_str(API_KEY_ENV).unwrap()) {
-            Ok(val) => Ok(CurrencybeaconConnector {
-                http_client: reqwest::blocking::Client::new(),
-                api_key: val,
-            }),
-            Err(_) => Err(Report::new(ConnectorError::ApiKeyRequirements)
+        match get_api_key(connector)? {
+            Some(val) => Ok(CurrencybeaconConnector::with_keys(
+                ApiKeys::from_env(&val)?,
+                HttpClient::new(connector)?,
+            )),
+            None => Err(Report::new(ConnectorError::ApiKeyRequirements)
                 .attach_printable("This connector require api key")),
         }
     }
-    fn make_request(&self, url: &str) -> Result<JsonValue, ConnectorError> {
-        let response = self.http_client.get(url).send();
-        match response {
-            Ok(response) => {
-                match response.status() {
-                    StatusCode::OK => {
-                        let text = response.text().change_context(ConnectorError::ParseError)?;
-                        return json::parse(&text)
-                            .change_context(ConnectorError::JsonParsingError)
-                            .attach_printable(format!("Error during json parsing:\n{text}"));
-                    }
-                    StatusCode::UNAUTHORIZED => Err(Report::new(ApiError::AuthorizationError)
-                        .attach_printable("Authorization error")),
-                    StatusCode::INTERNAL_SERVER_ERROR => {
-                        Err(Report::new(ApiError::ServerError).attach_printable("Server error"))
-                    }
-                    StatusCode::TOO_MANY_REQUESTS => Err(Report::new(ApiError::TooManyRequests)
-                        .attach_printable("Too many requests")),
-                    status_code => Err(Report::new(ApiError::SomethingElse)
-                        .attach_printable(format!("Status code : {status_code:?}"))),
+    /// Connector with api key, or several keys separated by commas
+    pub fn with_api_key(api_key: &str) -> Self {
+        CurrencybeaconConnector::with_client(api_key, HttpClient::live())
+    }
+    /// Connector sending requests with `http_client`, e.g. replaying recorded responses
+    pub fn with_client(api_key: &str, http_client: HttpClient) -> Self {
+        CurrencybeaconConnector::with_keys(ApiKeys::new(api_key), http_client)
+    }
+    /// Connector rotating through `api_keys`
+    pub fn with_keys(api_keys: ApiKeys, http_client: HttpClient) -> Self {
+        let http_client = api_keys
+            .keys()
+            .iter()
+            .fold(http_client, |http_client, key| http_client.secret(key));
+        CurrencybeaconConnector {
+            http_client,
+            api_keys,
+        }
+    }
+    /// Request `endpoint` with `query`, trying next keys while the current one is exhausted.
+    /// Api key is sent in `Authorization` header, so it never gets into urls.
+    #[tracing::instrument(name = "request", skip(self, query), fields(retries = tracing::field::Empty))]
+    async fn make_request(&self, endpoint: &str, query: &str) -> Result<JsonValue, ConnectorError> {
+        let url = if query.is_empty() {
+            format!("{BASE_URL}{endpoint}")
+        } else {
+            format!("{BASE_URL}{endpoint}?{query}")
+        };
+        let mut last_error = None;
+        let mut retries = 0;
+        for index in self.api_keys.candidates(Utc::now())? {
+            let result = self.send(&url, self.api_keys.key(index)).await;
+            if let Err(err) = &result {
+                if self.api_keys.exhaust_on(index, err, Utc::now()).await {
+                    last_error = result.err();
+                    retries += 1;
+                    continue;
                 }
-                .change_context(ConnectorError::ApiError)
             }
-            Err(err) => Err(err)
-                .change_context(ConnectorError::SendingError)
-                .attach_printable("Error with sending")?,
+            tracing::Span::current().record("retries", retries);
+            return result;
         }
+        tracing::Span::current().record("retries", retries);
+        Err(last_error
+            .unwrap_or_else(|| Report::new(ConnectorError::ApiKeyRequirements))
+            .attach_printable("All api keys are exhausted"))
+    }
+    async fn send(&self, url: &str, api_key: &str) -> Result<JsonValue, ConnectorError> {
+        let request = self.http_client.get(url).bearer_auth(api_key);
+        let json = self.http_client.get_json(request).await?;
+        check_meta(&json)?;
+        Ok(json)
+    }
+    async fn rates(
+        &self,
+        endpoint: &str,
+        query: &str,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let json = &self.make_request(endpoint, query).await?;
+        parse_rates(json, "rates")
     }
 }
 
-impl Connector for CurrencybeaconConnector {
-    fn exchange(
+#[async_trait]
+impl AsyncConnector for CurrencybeaconConnector {
+    async fn exchange(
         &self,
         source: &str,
         target: &str,
         amount: &BigDecimal,
     ) -> Result<ExchangeOutput, ConnectorError> {
-        let currency = self
-            .latest(source, Some(vec![target.to_string()]))?
-            .first()
-            .ok_or_else(|| Report::new(ConnectorError::ApiError))?
-            .clone();
-        let rate = currency.get_rate();
+        let target = target.to_uppercase();
+        let latest = self.latest(source, Some(vec![target.clone()])).await?;
+        let rate = latest
+            .iter()
+            .find(|latest| latest.get_currency().get_short_code() == target)
+            .map(LatestOutput::get_rate)
+            .ok_or_else(|| {
+                Report::new(ApiError::InvalidCurrency)
+                    .attach_printable(format!(
+                        "No exchange rate for {source}/{target} in response"
+                    ))
+                    .change_context(ConnectorError::ApiError)
+            })?;
         Ok(ExchangeOutput::new(&(rate * amount), rate))
     }
 
-    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
-        let url = format!("{BASE_URL}currencies?api_key={}", self.api_key);
-        let json = self.make_request(&url)?;
-        Ok(json["response"]
-            .members()
-            .map(|v| Currency::new(&v["short_code"].to_string(), Some(v["name"].to_string())))
-            .collect::<Vec<Currency>>())
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        let json = self.make_request("currencies", "").await?;
+        parse_currencies(&json, "response", "short_code", "name")
     }
-    fn latest(
+    async fn latest(
         &self,
         base: &str,
         target: Option<Vec<String>>,
     ) -> Result<Vec<LatestOutput>, ConnectorError> {
-        let url = match target {
-            Some(target) => {
-                let target_str = target.join(",");
-                format!(
-                    "{BASE_URL}latest?api_key={}&base={base}&symbols={target_str}",
-                    self.api_key
-                )
-            }
-            None => {
-                format!("{BASE_URL}latest?api_key={}&base={base}", self.api_key)
-            }
-        };
-        let json = &self.make_request(&url)?;
-        Ok(json["rates"]
-            .entries()
-            .map(|(k, v)| {
-                LatestOutput::new(
-                    Currency::new(k, None),
-                    BigDecimal::from_str(&v.to_string()).unwrap(),
-                )
-            })
-            .collect::<Vec<_>>())
+        let query = format!("base={base}{}", symbols(target));
+        self.rates("latest", &query).await
+    }
+
+    async fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let query = format!(
+            "base={base}&date={}{}",
+            date.format("%Y-%m-%d"),
+            symbols(target)
+        );
+        self.rates("historical", &query).await
     }
 }
 
-#[derive(Debug)]
-pub enum ApiError {
-    ServerError,
-    TooManyRequests,
-    AuthorizationError,
-    SomethingElse,
+/// Currencybeacon reports errors in `meta` object, also in responses with status 200
+fn check_meta(json: &JsonValue) -> Result<(), ConnectorError> {
+    let meta = &json["meta"];
+    let Some(code) = meta["code"].as_u16() else {
+        return Ok(());
+    };
+    let error = match code {
+        200 => return Ok(()),
+        401 | 601 => ApiError::AuthorizationError,
+        422 | 602 => ApiError::InvalidCurrency,
+        429 => ApiError::QuotaExceeded,
+        603 => ApiError::PlanRestriction,
+        500 | 503 | 600 => ApiError::ServerError,
+        _ => ApiError::SomethingElse,
+    };
+    let detail = meta["error_detail"]
+        .as_str()
+        .or_else(|| meta["error_type"].as_str())
+        .unwrap_or("no details");
+    Err(Report::new(error)
+        .attach_printable(format!("Currencybeacon error {code}: {}", redact(detail)))
+        .change_context(ConnectorError::ApiError))
 }
-impl fmt::Display for ApiError {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt.write_str("Error with api")
+
+fn symbols(target: Option<Vec<String>>) -> String {
+    match target {
+        Some(target) => format!("&symbols={}", target.join(",")),
+        None => String::new(),
     }
 }
 
-impl Context for ApiError {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::http::HttpMode;
+    use std::path::Path;
+    use std::str::FromStr;
+
+    const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/currencybeacon.json");
+
+    fn connector() -> CurrencybeaconConnector {
+        let http_client = HttpClient::with_mode(HttpMode::Replay, Path::new(CASSETTE)).unwrap();
+        CurrencybeaconConnector::with_client("TEST_KEY", http_client)
+    }
+
+    fn codes(rates: &[LatestOutput]) -> Vec<&str> {
+        rates
+            .iter()
+            .map(|latest| latest.get_currency().get_short_code())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn list_currencies() {
+        assert_eq!(
+            vec![
+                Currency::new("EUR", Some("Euro".to_string())),
+                Currency::new("PLN", Some("Polish Zloty".to_string())),
+            ],
+            connector().list_currencies().await.unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn latest_without_symbols() {
+        let rates = connector().latest("USD", None).await.unwrap();
+        assert_eq!(vec!["EUR", "GBP", "PLN"], codes(&rates));
+    }
+
+    #[tokio::test]
+    async fn latest_with_symbols() {
+        let rates = connector()
+            .latest("USD", Some(vec!["PLN".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![LatestOutput::new(
+                Currency::new("PLN", None),
+                BigDecimal::from_str("3.98").unwrap()
+            )],
+            rates
+        );
+    }
+
+    #[tokio::test]
+    async fn exchange() {
+        assert_eq!(
+            ExchangeOutput::new(
+                &BigDecimal::from_str("39.8").unwrap(),
+                &BigDecimal::from_str("3.98").unwrap()
+            ),
+            connector()
+                .exchange("USD", "PLN", &BigDecimal::from(10))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn exchange_lowercase_target() {
+        assert_eq!(
+            ExchangeOutput::new(
+                &BigDecimal::from_str("39.8").unwrap(),
+                &BigDecimal::from_str("3.98").unwrap()
+            ),
+            connector()
+                .exchange("USD", "pln", &BigDecimal::from(10))
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_rate() {
+        let err = connector().latest("BAD", None).await.unwrap_err();
+        assert!(matches!(err.current_context(), ConnectorError::ParseError));
+    }
+
+    #[tokio::test]
+    async fn malformed_json() {
+        let err = connector().latest("EUR", None).await.unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::JsonParsingError
+        ));
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let err = connector().latest("GBP", None).await.unwrap_err();
+        assert!(!format!("{err:?}").contains("TEST_KEY"));
+        assert!(matches!(err.current_context(), ConnectorError::ApiError));
+        assert!(matches!(
+            err.downcast_ref::<ApiError>(),
+            Some(ApiError::AuthorizationError)
+        ));
+    }
+
+    #[tokio::test]
+    async fn errors_inside_ok_responses() {
+        let api_error = |err: Report<ConnectorError>| err.downcast_ref::<ApiError>().copied();
+        let connector = connector();
+        assert_eq!(
+            Some(ApiError::InvalidCurrency),
+            api_error(connector.latest("XYZ", None).await.unwrap_err())
+        );
+        assert_eq!(
+            Some(ApiError::PlanRestriction),
+            api_error(
+                connector
+                    .historical(NaiveDate::from_ymd_opt(1990, 1, 1).unwrap(), "USD", None)
+                    .await
+                    .unwrap_err()
+            )
+        );
+        assert_eq!(
+            Some(ApiError::QuotaExceeded),
+            api_error(connector.latest("CHF", None).await.unwrap_err())
+        );
+    }
+
+    #[tokio::test]
+    async fn exhausted_keys_skipped() {
+        let http_client = HttpClient::with_mode(HttpMode::Replay, Path::new(CASSETTE)).unwrap();
+        let connector = CurrencybeaconConnector::with_client("KEY_A,KEY_B", http_client);
+        let err = connector.latest("CHF", None).await.unwrap_err();
+        assert_eq!(
+            Some(&ApiError::QuotaExceeded),
+            err.downcast_ref::<ApiError>()
+        );
+        let err = connector.latest("USD", None).await.unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::ApiKeyRequirements
+        ));
+    }
+}
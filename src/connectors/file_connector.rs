@@ -0,0 +1,425 @@
+use crate::connector::{Connector, ConnectorError};
+use crate::connector_output::{ExchangeOutput, LatestOutput};
+use crate::currency::Currency;
+use crate::utility::{get_rates_file_path, round_trimmed};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, Zero};
+use chrono::NaiveDate;
+use error_stack::{Report, Result, ResultExt};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+const INVERSE_RATE_SCALE: i64 = 10;
+
+struct RateRow {
+    date: Option<NaiveDate>,
+    base: String,
+    target: String,
+    rate: BigDecimal,
+}
+
+impl RateRow {
+    /// Read row from fields `date` (optional), `base`, `target` and `rate`
+    fn read(field: impl Fn(&str) -> Option<String>) -> Result<Self, ConnectorError> {
+        let field = |name: &str| {
+            field(name)
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let required = |name: &str| {
+            field(name).ok_or_else(|| {
+                Report::new(ConnectorError::ParseError).attach_printable(format!("Missing {name}"))
+            })
+        };
+        let rate = required("rate")?;
+        let rate = BigDecimal::from_str(&rate)
+            .change_context(ConnectorError::ParseError)
+            .attach_printable_lazy(|| format!("Invalid rate {rate}"))?;
+        if rate <= BigDecimal::zero() {
+            return Err(Report::new(ConnectorError::ParseError)
+                .attach_printable(format!("Rate {rate} is not positive")));
+        }
+        Ok(RateRow {
+            date: field("date")
+                .map(|date| {
+                    NaiveDate::from_str(&date)
+                        .change_context(ConnectorError::ParseError)
+                        .attach_printable_lazy(|| format!("Invalid date {date}"))
+                })
+                .transpose()?,
+            base: required("base")?.to_uppercase(),
+            target: required("target")?.to_uppercase(),
+            rate,
+        })
+    }
+}
+
+/// Serves exchange rates from a local csv, json or toml file.
+///
+/// Every row has `base`, `target`, `rate` and optional `date`. A dated rate is used from its date
+/// until the next one, rows without date apply to every date. Rates are also used in reverse.
+///
+/// ```csv
+/// date,base,target,rate
+/// 2026-01-01,USD,PLN,4.01
+/// 2026-01-02,USD,PLN,3.98
+/// ,EUR,PLN,4.25
+/// ```
+///
+/// Json file is a list of rows or an object with `rates` list and `currencies` map of names,
+/// toml file has `[[rates]]` tables and `[currencies]` table.
+pub struct FileConnector {
+    currencies: BTreeMap<String, Option<String>>,
+    /// Rates of every pair sorted by date, undated first
+    rates: HashMap<(String, String), Vec<DatedRate>>,
+}
+
+/// Rate valid from its date onward, or on every date when undated
+type DatedRate = (Option<NaiveDate>, BigDecimal);
+
+fn read_rows<T>(
+    rows: impl Iterator<Item = T>,
+    field: impl Fn(&T, &str) -> Option<String>,
+) -> Result<Vec<RateRow>, ConnectorError> {
+    rows.enumerate()
+        .map(|(i, row)| {
+            RateRow::read(|name| field(&row, name))
+                .attach_printable_lazy(|| format!("Error in row {}", i + 1))
+        })
+        .try_collect::<Vec<_>>()
+}
+
+impl FileConnector {
+    pub fn new() -> Result<Self, ConnectorError> {
+        FileConnector::open(&get_rates_file_path())
+    }
+
+    /// Read rates file, format is chosen by extension: `csv`, `json` or `toml`
+    pub fn open(path: &Path) -> Result<Self, ConnectorError> {
+        let text = fs::read_to_string(path)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("csv") => FileConnector::from_csv(&text),
+            Some("json") => FileConnector::from_json(&text),
+            Some("toml") => FileConnector::from_toml(&text),
+            _ => {
+                let msg = format!(
+                    "Unsupported rates file {}, expected csv, json or toml",
+                    path.display()
+                );
+                Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg))
+            }
+        }
+        .attach_printable_lazy(|| format!("Error in rates file {}", path.display()))
+    }
+
+    fn from_csv(text: &str) -> Result<Self, ConnectorError> {
+        let mut reader = csv::Reader::from_reader(text.as_bytes());
+        let headers = reader
+            .headers()
+            .change_context(ConnectorError::ParseError)?
+            .clone();
+        let records = reader
+            .records()
+            .map(|record| record.change_context(ConnectorError::ParseError))
+            .try_collect::<Vec<_>>()?;
+        let rows = read_rows(records.iter(), |record, name| {
+            let index = headers.iter().position(|header| header == name)?;
+            record.get(index).map(ToString::to_string)
+        })?;
+        Ok(FileConnector::from_rows(rows, BTreeMap::new()))
+    }
+
+    fn from_json(text: &str) -> Result<Self, ConnectorError> {
+        let json = json::parse(text).change_context(ConnectorError::JsonParsingError)?;
+        let rates = if json.is_array() {
+            &json
+        } else {
+            &json["rates"]
+        };
+        let rows = read_rows(rates.members(), |row, name| {
+            let value = &row[name];
+            (!value.is_null()).then(|| value.to_string())
+        })?;
+        let names = json["currencies"]
+            .entries()
+            .map(|(code, name)| (code.to_uppercase(), name.to_string()))
+            .collect();
+        Ok(FileConnector::from_rows(rows, names))
+    }
+
+    fn from_toml(text: &str) -> Result<Self, ConnectorError> {
+        let table = text
+            .parse::<toml::Table>()
+            .change_context(ConnectorError::ParseError)?;
+        let rates = table
+            .get("rates")
+            .and_then(toml::Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let rows = read_rows(rates.iter(), |row, name| match row.get(name)? {
+            toml::Value::String(value) => Some(value.clone()),
+            toml::Value::Datetime(date) => Some(date.to_string()),
+            value => Some(value.to_string()),
+        })?;
+        let names = table
+            .get("currencies")
+            .and_then(toml::Value::as_table)
+            .into_iter()
+            .flatten()
+            .filter_map(|(code, name)| Some((code.to_uppercase(), name.as_str()?.to_string())))
+            .collect();
+        Ok(FileConnector::from_rows(rows, names))
+    }
+
+    fn from_rows(rows: Vec<RateRow>, names: BTreeMap<String, String>) -> Self {
+        let mut currencies = BTreeMap::new();
+        let mut rates = HashMap::<_, Vec<_>>::new();
+        for row in rows {
+            currencies.insert(row.base.clone(), None);
+            currencies.insert(row.target.clone(), None);
+            rates
+                .entry((row.base, row.target))
+                .or_default()
+                .push((row.date, row.rate));
+        }
+        for pair_rates in rates.values_mut() {
+            pair_rates.sort_by_key(|(date, _)| *date);
+        }
+        for (code, name) in names {
+            currencies.insert(code, Some(name));
+        }
+        FileConnector { currencies, rates }
+    }
+
+    /// Rates from `base` valid on `date`, the newest ones without date
+    fn rates(
+        &self,
+        base: &str,
+        date: Option<NaiveDate>,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let rate_on = |rates: &[(Option<NaiveDate>, BigDecimal)]| {
+            rates
+                .iter()
+                .rev()
+                .find(|(rate_date, _)| date.is_none() || *rate_date <= date)
+                .map(|(_, rate)| rate.clone())
+        };
+        let mut found = BTreeMap::new();
+        for ((from, to), rates) in &self.rates {
+            let Some(rate) = rate_on(rates) else {
+                continue;
+            };
+            if from == base {
+                found.insert(to.clone(), rate);
+            } else if to == base {
+                found.entry(from.clone()).or_insert_with(|| {
+                    round_trimmed(&(BigDecimal::from(1) / rate), INVERSE_RATE_SCALE)
+                });
+            }
+        }
+        if found.is_empty() {
+            let msg = match date {
+                Some(date) => format!("No rates for currency code {base} on {date}"),
+                None => format!("No rates for currency code {base}"),
+            };
+            return Err(
+                Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+            );
+        }
+        let output = |(code, rate): (&String, &BigDecimal)| {
+            let name = self.currencies.get(code).cloned().flatten();
+            LatestOutput::new(Currency::new(code, name), rate.clone())
+        };
+        match target {
+            Some(target) => target
+                .iter()
+                .map(|code| {
+                    found.get_key_value(code).map(output).ok_or_else(|| {
+                        let msg = format!("No rate for {base}/{code}");
+                        Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+                    })
+                })
+                .try_collect::<Vec<_>>(),
+            None => Ok(found.iter().map(output).collect()),
+        }
+    }
+}
+
+impl Connector for FileConnector {
+    fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        if source == target && self.currencies.contains_key(source) {
+            return Ok(ExchangeOutput::new(amount, &BigDecimal::from(1)));
+        }
+        let latest = self.rates(source, None, Some(vec![target.to_string()]))?;
+        let rate = latest[0].get_rate();
+        Ok(ExchangeOutput::new(&(amount * rate), rate))
+    }
+
+    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        Ok(self
+            .currencies
+            .iter()
+            .map(|(code, name)| Currency::new(code, name.clone()))
+            .collect())
+    }
+
+    fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.rates(base, None, target)
+    }
+
+    fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.rates(base, Some(date), target)
+    }
+}
+
+/// Rates are held in memory, so they are served directly on the calling task
+#[async_trait]
+impl crate::async_connector::AsyncConnector for FileConnector {
+    async fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        Connector::exchange(self, source, target, amount)
+    }
+
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        Connector::list_currencies(self)
+    }
+
+    async fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        Connector::latest(self, base, target)
+    }
+
+    async fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        Connector::historical(self, date, base, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 1, day).unwrap()
+    }
+
+    fn pln(connector: &FileConnector, date: Option<NaiveDate>) -> BigDecimal {
+        connector
+            .rates("USD", date, Some(vec!["PLN".to_string()]))
+            .unwrap()[0]
+            .get_rate()
+            .clone()
+    }
+
+    #[test]
+    fn dated_csv_rows() {
+        let connector = FileConnector::from_csv(
+            "date,base,target,rate\n\
+             2026-01-03,USD,PLN,5\n\
+             2026-01-01,usd,pln,4\n",
+        )
+        .unwrap();
+        assert_eq!(BigDecimal::from(4), pln(&connector, Some(date(2))));
+        assert_eq!(BigDecimal::from(5), pln(&connector, None));
+        let before_first = NaiveDate::from_ymd_opt(2025, 12, 31).unwrap();
+        assert!(connector.historical(before_first, "USD", None).is_err());
+    }
+
+    #[test]
+    fn undated_and_reverse_rates() {
+        let connector = FileConnector::from_json(
+            r#"{"rates": [{"base": "USD", "target": "PLN", "rate": 4},
+                          {"base": "GBP", "target": "USD", "rate": "1.25"}],
+                "currencies": {"USD": "US Dollar"}}"#,
+        )
+        .unwrap();
+        assert_eq!(BigDecimal::from(4), pln(&connector, Some(date(1))));
+        assert_eq!(
+            vec![
+                LatestOutput::new(
+                    Currency::new("GBP", None),
+                    BigDecimal::from_str("0.8").unwrap()
+                ),
+                LatestOutput::new(Currency::new("PLN", None), BigDecimal::from(4)),
+            ],
+            connector.latest("USD", None).unwrap()
+        );
+        assert_eq!(
+            Some("US Dollar"),
+            connector.list_currencies().unwrap()[2].get_name()
+        );
+    }
+
+    #[test]
+    fn toml_rows() {
+        let connector = FileConnector::from_toml(
+            "[currencies]\nPLN = \"Zloty\"\n\n\
+             [[rates]]\ndate = 2026-01-01\nbase = \"USD\"\ntarget = \"PLN\"\nrate = 4\n",
+        )
+        .unwrap();
+        assert_eq!(BigDecimal::from(4), pln(&connector, Some(date(1))));
+        assert_eq!(
+            &Currency::new("PLN", Some("Zloty".to_string())),
+            connector.latest("USD", None).unwrap()[0].get_currency()
+        );
+    }
+
+    #[test]
+    fn invalid_row() {
+        assert!(FileConnector::from_csv("base,target,rate\nUSD,PLN,four\n").is_err());
+        assert!(FileConnector::from_csv("base,rate\nUSD,4\n").is_err());
+        for rate in ["0", "-4"] {
+            let err = FileConnector::from_csv(&format!(
+                "base,target,rate\nUSD,GBP,0.8\nUSD,PLN,{rate}\n"
+            ))
+            .err()
+            .unwrap();
+            assert!(matches!(err.current_context(), ConnectorError::ParseError));
+            assert!(format!("{err:?}").contains("Error in row 2"));
+        }
+    }
+
+    #[test]
+    fn same_currency() {
+        let connector = FileConnector::from_csv("base,target,rate\nUSD,PLN,4\n").unwrap();
+        assert_eq!(
+            ExchangeOutput::new(&BigDecimal::from(10), &BigDecimal::from(1)),
+            Connector::exchange(&connector, "PLN", "PLN", &BigDecimal::from(10)).unwrap()
+        );
+        assert!(Connector::exchange(&connector, "XYZ", "XYZ", &BigDecimal::from(10)).is_err());
+    }
+}
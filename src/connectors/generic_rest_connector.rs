@@ -0,0 +1,579 @@
+use crate::async_connector::AsyncConnector;
+use crate::connector::{ApiError, ConnectorEnum, ConnectorError, ExchangeOutput, LatestOutput};
+use crate::connectors::http::HttpClient;
+use crate::connectors::response::{lookup, parse_currencies, parse_rates};
+use crate::currency::Currency;
+use crate::utility::{get_api_key, get_rest_config_path};
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::fs;
+use std::path::Path;
+
+/// Url template and json path of the value read from the response
+#[derive(Debug, PartialEq)]
+struct Endpoint {
+    url: String,
+    path: String,
+}
+
+#[derive(Debug, PartialEq)]
+struct CurrenciesEndpoint {
+    endpoint: Endpoint,
+    code: String,
+    name: String,
+}
+
+/// Error object returned by the api instead of rates, also with status 200
+#[derive(Debug, PartialEq)]
+struct ErrorEnvelope {
+    /// Response is an error when this field is present and not `false`
+    field: String,
+    code: String,
+    message: String,
+    /// Error codes of the api for every [`ApiError`]
+    codes: Vec<(ApiError, Vec<String>)>,
+}
+
+const ERROR_CODES: [(&str, ApiError); 6] = [
+    ("authorization", ApiError::AuthorizationError),
+    ("invalid_currency", ApiError::InvalidCurrency),
+    ("plan_restriction", ApiError::PlanRestriction),
+    ("quota_exceeded", ApiError::QuotaExceeded),
+    ("too_many_requests", ApiError::TooManyRequests),
+    ("server_error", ApiError::ServerError),
+];
+
+#[derive(Debug, PartialEq)]
+enum ApiKeyPlacement {
+    None,
+    Query(String),
+    Header(String),
+}
+
+/// Description of a rest api, read from a toml file, e.g.
+///
+/// ```toml
+/// name = "openexchangerates"
+///
+/// [api_key]
+/// query = "app_id"
+///
+/// [latest]
+/// url = "https://openexchangerates.org/api/latest.json?base={base}&symbols={symbols}"
+/// rates = "rates"
+///
+/// [historical]
+/// url = "https://openexchangerates.org/api/historical/{date}.json?base={base}&symbols={symbols}"
+/// rates = "rates"
+///
+/// [currencies]
+/// url = "https://openexchangerates.org/api/currencies.json"
+/// list = ""
+///
+/// [error]
+/// field = "error"
+/// code = "message"
+/// message = "description"
+/// authorization = ["invalid_app_id", "missing_app_id"]
+/// invalid_currency = ["invalid_base"]
+/// plan_restriction = ["not_allowed"]
+/// quota_exceeded = ["access_restricted"]
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct RestConfig {
+    name: String,
+    api_key: ApiKeyPlacement,
+    latest: Endpoint,
+    historical: Option<Endpoint>,
+    currencies: Option<CurrenciesEndpoint>,
+    /// Rates keys contain base code, e.g. `USDPLN`
+    base_prefixed_rates: bool,
+    error: Option<ErrorEnvelope>,
+}
+
+fn invalid_input(msg: String) -> Report<ConnectorError> {
+    Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+}
+
+fn get_str<'a>(table: &'a toml::Table, section: &str, key: &str) -> Option<&'a str> {
+    table.get(section)?.as_table()?.get(key)?.as_str()
+}
+
+fn endpoint(
+    table: &toml::Table,
+    section: &str,
+    path_key: &str,
+) -> Result<Option<Endpoint>, ConnectorError> {
+    if !table.contains_key(section) {
+        return Ok(None);
+    }
+    let url = get_str(table, section, "url")
+        .ok_or_else(|| invalid_input(format!("Missing {section}.url in rest connector config")))?;
+    Ok(Some(Endpoint {
+        url: url.to_string(),
+        path: get_str(table, section, path_key)
+            .unwrap_or_default()
+            .to_string(),
+    }))
+}
+
+fn toml_string(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn error_envelope(table: &toml::Table) -> Option<ErrorEnvelope> {
+    let error = table.get("error")?.as_table()?;
+    let path = |key: &str| {
+        error
+            .get(key)
+            .and_then(toml::Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    let codes = ERROR_CODES
+        .iter()
+        .filter_map(|(key, api_error)| {
+            let codes = error.get(*key)?.as_array()?;
+            Some((*api_error, codes.iter().map(toml_string).collect()))
+        })
+        .collect();
+    Some(ErrorEnvelope {
+        field: error
+            .get("field")
+            .and_then(toml::Value::as_str)
+            .unwrap_or("error")
+            .to_string(),
+        code: path("code"),
+        message: path("message"),
+        codes,
+    })
+}
+
+/// Value of json field as written in the config, strings without quotes
+fn json_text(value: &JsonValue) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.dump(), ToString::to_string)
+}
+
+impl RestConfig {
+    pub fn load(path: &Path) -> Result<Self, ConnectorError> {
+        let text = fs::read_to_string(path)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+        RestConfig::from_toml(&text)
+            .attach_printable_lazy(|| format!("Error in rest connector config {}", path.display()))
+    }
+
+    fn from_toml(text: &str) -> Result<Self, ConnectorError> {
+        let table = text
+            .parse::<toml::Table>()
+            .change_context(ConnectorError::ParseError)?;
+        let api_key = match (
+            get_str(&table, "api_key", "query"),
+            get_str(&table, "api_key", "header"),
+        ) {
+            (Some(name), None) => ApiKeyPlacement::Query(name.to_string()),
+            (None, Some(name)) => ApiKeyPlacement::Header(name.to_string()),
+            (None, None) => ApiKeyPlacement::None,
+            (Some(_), Some(_)) => {
+                return Err(invalid_input(
+                    "Api key goes either to query or header".to_string(),
+                ))
+            }
+        };
+        let latest = endpoint(&table, "latest", "rates")?
+            .ok_or_else(|| invalid_input("Missing latest endpoint".to_string()))?;
+        let currencies =
+            endpoint(&table, "currencies", "list")?.map(|endpoint| CurrenciesEndpoint {
+                endpoint,
+                code: get_str(&table, "currencies", "code")
+                    .unwrap_or("code")
+                    .to_string(),
+                name: get_str(&table, "currencies", "name")
+                    .unwrap_or("name")
+                    .to_string(),
+            });
+        Ok(RestConfig {
+            name: table
+                .get("name")
+                .and_then(toml::Value::as_str)
+                .unwrap_or("rest")
+                .to_string(),
+            api_key,
+            latest,
+            historical: endpoint(&table, "historical", "rates")?,
+            currencies,
+            base_prefixed_rates: table
+                .get("base_prefixed_rates")
+                .and_then(toml::Value::as_bool)
+                .unwrap_or_default(),
+            error: error_envelope(&table),
+        })
+    }
+}
+
+/// Connector for any rest api described by [`RestConfig`]
+pub struct GenericRestConnector {
+    http_client: HttpClient,
+    config: RestConfig,
+    api_key: Option<String>,
+}
+
+impl GenericRestConnector {
+    pub fn new() -> Result<Self, ConnectorError> {
+        GenericRestConnector::open(
+            &get_rest_config_path(),
+            GenericRestConnector::env_api_key()?,
+        )
+    }
+    /// Api key from `EAPP_GENERIC_REST` or the secrets store
+    pub fn env_api_key() -> Result<Option<String>, ConnectorError> {
+        get_api_key(ConnectorEnum::GenericRest)
+    }
+    pub fn open(path: &Path, api_key: Option<String>) -> Result<Self, ConnectorError> {
+        GenericRestConnector::with_client(
+            RestConfig::load(path)?,
+            api_key,
+            HttpClient::new(ConnectorEnum::GenericRest)?,
+        )
+    }
+    pub fn with_config(
+        config: RestConfig,
+        api_key: Option<String>,
+    ) -> Result<Self, ConnectorError> {
+        GenericRestConnector::with_client(config, api_key, HttpClient::live())
+    }
+    pub fn with_client(
+        config: RestConfig,
+        api_key: Option<String>,
+        http_client: HttpClient,
+    ) -> Result<Self, ConnectorError> {
+        if config.api_key != ApiKeyPlacement::None && api_key.is_none() {
+            return Err(Report::new(ConnectorError::ApiKeyRequirements)
+                .attach_printable(format!("Connector {} require api key", config.name)));
+        }
+        Ok(GenericRestConnector {
+            http_client: http_client.secret(api_key.as_deref().unwrap_or_default()),
+            config,
+            api_key,
+        })
+    }
+    fn url(
+        template: &str,
+        base: &str,
+        target: Option<Vec<String>>,
+        date: Option<NaiveDate>,
+    ) -> String {
+        template
+            .replace("{base}", base)
+            .replace("{symbols}", &target.unwrap_or_default().join(","))
+            .replace(
+                "{date}",
+                &date
+                    .map(|date| date.format("%Y-%m-%d").to_string())
+                    .unwrap_or_default(),
+            )
+    }
+    async fn make_request(&self, url: &str) -> Result<JsonValue, ConnectorError> {
+        let request = self.http_client.get(url);
+        let request = match (&self.config.api_key, &self.api_key) {
+            (ApiKeyPlacement::Query(name), Some(api_key)) => request.query(&[(name, api_key)]),
+            (ApiKeyPlacement::Header(name), Some(api_key)) => request.header(name, api_key),
+            _ => request,
+        };
+        let json = self
+            .http_client
+            .get_json(request)
+            .await
+            .attach_printable_lazy(|| format!("Error with {} api", self.config.name))?;
+        self.check_error(&json)?;
+        Ok(json)
+    }
+    fn check_error(&self, json: &JsonValue) -> Result<(), ConnectorError> {
+        let Some(envelope) = &self.config.error else {
+            return Ok(());
+        };
+        let field = lookup(json, &envelope.field);
+        if field.is_null() || *field == false {
+            return Ok(());
+        }
+        let code = json_text(lookup(json, &envelope.code));
+        let api_error = envelope
+            .codes
+            .iter()
+            .find(|(_, codes)| codes.contains(&code))
+            .map_or(ApiError::SomethingElse, |(api_error, _)| *api_error);
+        let message = lookup(json, &envelope.message);
+        Err(Report::new(api_error)
+            .attach_printable(format!(
+                "{} error {code}: {}",
+                self.config.name,
+                json_text(message)
+            ))
+            .change_context(ConnectorError::ApiError))
+    }
+    fn rates(
+        &self,
+        json: &JsonValue,
+        path: &str,
+        base: &str,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let rates = parse_rates(json, path)?;
+        if !self.config.base_prefixed_rates {
+            return Ok(rates);
+        }
+        Ok(rates
+            .into_iter()
+            .map(|latest| {
+                let code = latest.get_currency().get_short_code();
+                let code = code.strip_prefix(base).unwrap_or(code);
+                LatestOutput::new(Currency::new(code, None), latest.get_rate().clone())
+            })
+            .collect())
+    }
+    fn currencies(
+        json: &JsonValue,
+        endpoint: &CurrenciesEndpoint,
+    ) -> Result<Vec<Currency>, ConnectorError> {
+        parse_currencies(
+            json,
+            &endpoint.endpoint.path,
+            &endpoint.code,
+            &endpoint.name,
+        )
+    }
+}
+
+#[async_trait]
+impl AsyncConnector for GenericRestConnector {
+    async fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        let target = target.to_uppercase();
+        let latest = self.latest(source, Some(vec![target.clone()])).await?;
+        let rate = latest
+            .iter()
+            .find(|latest| latest.get_currency().get_short_code() == target)
+            .map(LatestOutput::get_rate)
+            .ok_or_else(|| invalid_input(format!("No exchange rate for {source}/{target}")))?;
+        Ok(ExchangeOutput::new(&(rate * amount), rate))
+    }
+
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        let endpoint = self.config.currencies.as_ref().ok_or_else(|| {
+            invalid_input(format!(
+                "Connector {} has no currencies endpoint",
+                self.config.name
+            ))
+        })?;
+        let json = self.make_request(&endpoint.endpoint.url).await?;
+        GenericRestConnector::currencies(&json, endpoint)
+    }
+
+    async fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let endpoint = &self.config.latest;
+        let url = GenericRestConnector::url(&endpoint.url, base, target, None);
+        let json = self.make_request(&url).await?;
+        self.rates(&json, &endpoint.path, base)
+    }
+
+    async fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let endpoint = self.config.historical.as_ref().ok_or_else(|| {
+            invalid_input(format!(
+                "Connector {} has no historical endpoint",
+                self.config.name
+            ))
+        })?;
+        let url = GenericRestConnector::url(&endpoint.url, base, target, Some(date));
+        let json = self.make_request(&url).await?;
+        self.rates(&json, &endpoint.path, base)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::http::HttpMode;
+    use std::str::FromStr;
+
+    const CASSETTE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/generic_rest.json");
+
+    const EXCHANGERATE_HOST: &str = r#"
+        name = "exchangerate.host"
+        base_prefixed_rates = true
+
+        [api_key]
+        query = "access_key"
+
+        [latest]
+        url = "https://api.exchangerate.host/live?source={base}&currencies={symbols}"
+        rates = "quotes"
+
+        [historical]
+        url = "https://api.exchangerate.host/historical?date={date}&source={base}"
+        rates = "quotes"
+
+        [currencies]
+        url = "https://api.exchangerate.host/list"
+        list = "currencies"
+
+        [error]
+        field = "error"
+        code = "error.code"
+        message = "error.info"
+        authorization = [101]
+        invalid_currency = [201, 202]
+        quota_exceeded = [104]
+        plan_restriction = [105]
+    "#;
+
+    fn connector(config: &str) -> GenericRestConnector {
+        GenericRestConnector::with_config(
+            RestConfig::from_toml(config).unwrap(),
+            Some("API_KEY".to_string()),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn config() {
+        let config = RestConfig::from_toml(EXCHANGERATE_HOST).unwrap();
+        assert_eq!(
+            ApiKeyPlacement::Query("access_key".to_string()),
+            config.api_key
+        );
+        assert_eq!("quotes", config.latest.path);
+        assert!(config.base_prefixed_rates);
+        assert!(RestConfig::from_toml("name = \"no endpoints\"").is_err());
+    }
+
+    #[test]
+    fn api_key_required() {
+        let config = RestConfig::from_toml(EXCHANGERATE_HOST).unwrap();
+        assert!(GenericRestConnector::with_config(config, None).is_err());
+    }
+
+    #[test]
+    fn url_template() {
+        let date = NaiveDate::from_ymd_opt(2026, 1, 2);
+        assert_eq!(
+            "https://api.exchangerate.host/historical?date=2026-01-02&source=USD",
+            GenericRestConnector::url(
+                &connector(EXCHANGERATE_HOST).config.historical.unwrap().url,
+                "USD",
+                None,
+                date
+            )
+        );
+    }
+
+    #[test]
+    fn prefixed_rates() {
+        let json = json::parse(r#"{"success": true, "quotes": {"USDPLN": 4.1, "USDEUR": "0.9"}}"#)
+            .unwrap();
+        assert_eq!(
+            vec![
+                LatestOutput::new(
+                    Currency::new("PLN", None),
+                    BigDecimal::from_str("4.1").unwrap()
+                ),
+                LatestOutput::new(
+                    Currency::new("EUR", None),
+                    BigDecimal::from_str("0.9").unwrap()
+                ),
+            ],
+            connector(EXCHANGERATE_HOST)
+                .rates(&json, "quotes", "USD")
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn exchange() {
+        let http_client = HttpClient::with_mode(HttpMode::Replay, Path::new(CASSETTE)).unwrap();
+        let connector = GenericRestConnector::with_client(
+            RestConfig::from_toml(EXCHANGERATE_HOST).unwrap(),
+            Some("API_KEY".to_string()),
+            http_client,
+        )
+        .unwrap();
+        let expected = ExchangeOutput::new(
+            &BigDecimal::from_str("41").unwrap(),
+            &BigDecimal::from_str("4.1").unwrap(),
+        );
+        for target in ["PLN", "pln"] {
+            assert_eq!(
+                expected,
+                connector
+                    .exchange("USD", target, &BigDecimal::from(10))
+                    .await
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn missing_rates() {
+        let json = json::parse(r#"{"error": {"code": 101}}"#).unwrap();
+        assert!(connector(EXCHANGERATE_HOST)
+            .rates(&json, "quotes", "USD")
+            .is_err());
+    }
+
+    #[test]
+    fn currencies_from_map_and_list() {
+        let endpoint = |path: &str| CurrenciesEndpoint {
+            endpoint: Endpoint {
+                url: String::new(),
+                path: path.to_string(),
+            },
+            code: "short_code".to_string(),
+            name: "name".to_string(),
+        };
+        let map = json::parse(r#"{"USD": "United States Dollar"}"#).unwrap();
+        let list =
+            json::parse(r#"{"response": [{"short_code": "USD", "name": "United States Dollar"}]}"#)
+                .unwrap();
+        assert_eq!(
+            GenericRestConnector::currencies(&map, &endpoint("")).unwrap(),
+            GenericRestConnector::currencies(&list, &endpoint("response")).unwrap()
+        );
+    }
+
+    #[test]
+    fn error_inside_ok_response() {
+        let connector = connector(EXCHANGERATE_HOST);
+        let json = json::parse(
+            r#"{"success": false, "error": {"code": 201, "type": "invalid_source_currency",
+                "info": "You have supplied an invalid Source Currency."}}"#,
+        )
+        .unwrap();
+        let err = connector.check_error(&json).unwrap_err();
+        assert_eq!(
+            Some(&ApiError::InvalidCurrency),
+            err.downcast_ref::<ApiError>()
+        );
+        assert!(format!("{err:?}").contains("invalid Source Currency"));
+        let ok = json::parse(r#"{"success": true, "quotes": {"USDPLN": 4.1}}"#).unwrap();
+        assert!(connector.check_error(&ok).is_ok());
+    }
+}
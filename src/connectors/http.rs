@@ -0,0 +1,390 @@
+use crate::connector::{ApiError, ConnectorEnum, ConnectorError};
+use crate::connectors::rate_limit::RateLimiter;
+use crate::redact::{self, REDACTED};
+use crate::usage::UsageTracker;
+use crate::utility::{get_http_cassette_path, get_http_mode};
+use chrono::Utc;
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use reqwest::StatusCode;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Instant;
+use strum_macros::{Display, EnumString};
+use tracing::field::Empty;
+use tracing::Instrument;
+
+/// How [`HttpClient`] gets responses
+#[derive(Clone, Debug, PartialEq, Eq, Default, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum HttpMode {
+    /// Send requests to the api
+    #[default]
+    Live,
+    /// Send requests to the api and save responses to the cassette file
+    Record,
+    /// Serve responses from the cassette file without network
+    Replay,
+}
+
+/// Saved response, secrets in url and body are replaced with `REDACTED`
+struct Interaction {
+    url: String,
+    status: u16,
+    body: String,
+}
+
+/// Http client of the connectors which can record responses to a cassette file and replay them
+/// offline, e.g. for tests.
+///
+/// Mode and cassette are read from `EAPP_HTTP_MODE` (`live`, `record` or `replay`)
+/// and `EAPP_HTTP_CASSETTE`.
+pub struct HttpClient {
+    client: reqwest::Client,
+    mode: HttpMode,
+    cassette: PathBuf,
+    secrets: Vec<String>,
+    interactions: Mutex<Vec<Interaction>>,
+    usage: Option<UsageTracker>,
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl HttpClient {
+    /// Client of `connector` with mode from env, requests sent to the api are counted in its usage
+    /// and limited by its rate limit
+    pub fn new(connector: ConnectorEnum) -> Result<Self, ConnectorError> {
+        let mode = get_http_mode();
+        let mode = HttpMode::from_str(&mode)
+            .change_context_lazy(|| {
+                ConnectorError::InvalidInput(format!("Invalid http mode {mode}"))
+            })
+            .attach_printable("Expected live, record or replay")?;
+        let client = HttpClient::with_mode(mode, &get_http_cassette_path())?
+            .usage(UsageTracker::new(connector)?);
+        Ok(match RateLimiter::new(connector)? {
+            Some(rate_limiter) => client.rate_limit(rate_limiter),
+            None => client,
+        })
+    }
+    pub fn live() -> Self {
+        HttpClient {
+            client: reqwest::Client::new(),
+            mode: HttpMode::Live,
+            cassette: PathBuf::new(),
+            secrets: Vec::new(),
+            interactions: Mutex::new(Vec::new()),
+            usage: None,
+            rate_limiter: None,
+        }
+    }
+    pub fn with_mode(mode: HttpMode, cassette: &Path) -> Result<Self, ConnectorError> {
+        let interactions = if mode == HttpMode::Live || !cassette.exists() {
+            Vec::new()
+        } else {
+            load_cassette(cassette)?
+        };
+        Ok(HttpClient {
+            client: reqwest::Client::new(),
+            mode,
+            cassette: cassette.to_path_buf(),
+            secrets: Vec::new(),
+            interactions: Mutex::new(interactions),
+            usage: None,
+            rate_limiter: None,
+        })
+    }
+    /// Value which never gets into a cassette, logs or error reports, e.g. api key
+    #[must_use]
+    pub fn secret(mut self, secret: &str) -> Self {
+        if !secret.is_empty() {
+            redact::register(secret);
+            self.secrets.push(secret.to_string());
+        }
+        self
+    }
+    /// Count requests sent to the api, replayed responses are not counted
+    #[must_use]
+    pub fn usage(mut self, usage: UsageTracker) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+    /// Limit requests sent to the api, replayed responses are not limited
+    #[must_use]
+    pub fn rate_limit(mut self, rate_limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(rate_limiter);
+        self
+    }
+    pub fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        self.client.get(url)
+    }
+    fn scrub(&self, text: &str) -> String {
+        let text = self.secrets.iter().fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        });
+        redact::redact(&text)
+    }
+    /// Send `request` and parse json body, status codes other than 200 are reported as [`ApiError`].
+    ///
+    /// Request is traced in an `http` span with redacted url, mode, status and latency.
+    pub async fn get_json(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<JsonValue, ConnectorError> {
+        let request = request
+            .build()
+            .change_context(ConnectorError::SendingError)
+            .attach_printable("Error with building request")?;
+        let url = self.scrub(request.url().as_str());
+        let span = tracing::info_span!(
+            "http",
+            method = %request.method(),
+            url,
+            mode = %self.mode,
+            status = Empty,
+            latency_ms = Empty
+        );
+        let start = Instant::now();
+        let response = self.fetch(request, url).instrument(span.clone()).await;
+        span.record("latency_ms", start.elapsed().as_secs_f64() * 1000.0);
+        let (status, text) = response?;
+        span.record("status", status.as_u16());
+        response_json(status, &text).map_err(|err| {
+            let text = self.scrub(&text);
+            err.attach_printable(format!("Response:\n{text}"))
+        })
+    }
+    /// Status and body of response to `request`, from the api or the cassette
+    async fn fetch(
+        &self,
+        request: reqwest::Request,
+        url: String,
+    ) -> Result<(StatusCode, String), ConnectorError> {
+        if self.mode != HttpMode::Replay {
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire().await?;
+            }
+            if let Some(usage) = &self.usage {
+                usage.count_request(Utc::now().date_naive()).await?;
+            }
+        }
+        match self.mode {
+            HttpMode::Live => self.send(request).await,
+            HttpMode::Record => {
+                let (status, text) = self.send(request).await?;
+                self.record(Interaction {
+                    url,
+                    status: status.as_u16(),
+                    body: self.scrub(&text),
+                })?;
+                Ok((status, text))
+            }
+            HttpMode::Replay => self.replay(&url),
+        }
+    }
+    async fn send(
+        &self,
+        request: reqwest::Request,
+    ) -> Result<(StatusCode, String), ConnectorError> {
+        let url = self.scrub(request.url().as_str());
+        let response = self
+            .client
+            .execute(request)
+            .await
+            .map_err(reqwest::Error::without_url)
+            .change_context(ConnectorError::SendingError)
+            .attach_printable_lazy(|| format!("Error with sending request to {url}"))?;
+        let status = response.status();
+        let text = response
+            .text()
+            .await
+            .map_err(reqwest::Error::without_url)
+            .change_context(ConnectorError::ParseError)
+            .attach_printable_lazy(|| format!("Error with reading response of {url}"))?;
+        Ok((status, text))
+    }
+    fn record(&self, interaction: Interaction) -> Result<(), ConnectorError> {
+        let mut interactions = self
+            .interactions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        interactions.retain(|recorded| recorded.url != interaction.url);
+        interactions.push(interaction);
+        save_cassette(&self.cassette, &interactions)
+    }
+    fn replay(&self, url: &str) -> Result<(StatusCode, String), ConnectorError> {
+        let interactions = self
+            .interactions
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        let interaction = interactions
+            .iter()
+            .find(|interaction| interaction.url == url)
+            .ok_or_else(|| {
+                Report::new(ConnectorError::SendingError).attach_printable(format!(
+                    "No response for {url} in {}",
+                    self.cassette.display()
+                ))
+            })?;
+        let status = StatusCode::from_u16(interaction.status)
+            .change_context(ConnectorError::ParseError)
+            .attach_printable_lazy(|| format!("Invalid status in {}", self.cassette.display()))?;
+        Ok((status, interaction.body.clone()))
+    }
+}
+
+fn response_json(status: StatusCode, text: &str) -> Result<JsonValue, ConnectorError> {
+    let error = match status {
+        StatusCode::OK => {
+            return json::parse(text)
+                .change_context(ConnectorError::JsonParsingError)
+                .attach_printable("Error during json parsing");
+        }
+        StatusCode::UNAUTHORIZED => ApiError::AuthorizationError,
+        StatusCode::INTERNAL_SERVER_ERROR | StatusCode::SERVICE_UNAVAILABLE => {
+            ApiError::ServerError
+        }
+        StatusCode::TOO_MANY_REQUESTS => ApiError::TooManyRequests,
+        _ => ApiError::SomethingElse,
+    };
+    Err(Report::new(error)
+        .attach_printable(format!("Status code : {status}"))
+        .change_context(ConnectorError::ApiError))
+}
+
+/// Cassette is a json list of `{"url", "status", "body"}` objects
+fn load_cassette(path: &Path) -> Result<Vec<Interaction>, ConnectorError> {
+    let text = fs::read_to_string(path)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+    let json = json::parse(&text)
+        .change_context(ConnectorError::JsonParsingError)
+        .attach_printable_lazy(|| format!("Error in cassette {}", path.display()))?;
+    json.members()
+        .map(|interaction| {
+            Ok(Interaction {
+                url: interaction["url"].as_str().unwrap_or_default().to_string(),
+                status: interaction["status"].as_u16().ok_or_else(|| {
+                    Report::new(ConnectorError::ParseError)
+                        .attach_printable(format!("Invalid status in cassette {}", path.display()))
+                })?,
+                body: interaction["body"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .try_collect::<Vec<_>>()
+}
+
+fn save_cassette(path: &Path, interactions: &[Interaction]) -> Result<(), ConnectorError> {
+    let json = JsonValue::Array(
+        interactions
+            .iter()
+            .map(|interaction| {
+                json::object! {
+                    url: interaction.url.as_str(),
+                    status: interaction.status,
+                    body: interaction.body.as_str(),
+                }
+            })
+            .collect(),
+    );
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).change_context(ConnectorError::FileError)?;
+    }
+    fs::write(path, json.pretty(2))
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[tokio::test]
+    async fn record_and_replay_without_secret() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/latest?api_key=SECRET",
+            listener.local_addr().unwrap()
+        );
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let body = r#"{"key": "SECRET", "rates": {"PLN": 4}}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        let dir = tempfile::tempdir().unwrap();
+        let cassette = dir.path().join("cassette.json");
+
+        let client = HttpClient::with_mode(HttpMode::Record, &cassette)
+            .unwrap()
+            .secret("SECRET");
+        let recorded = client.get_json(client.get(&url)).await.unwrap();
+        server.join().unwrap();
+        assert!(!fs::read_to_string(&cassette).unwrap().contains("SECRET"));
+
+        let client = HttpClient::with_mode(HttpMode::Replay, &cassette)
+            .unwrap()
+            .secret("SECRET");
+        let replayed = client.get_json(client.get(&url)).await.unwrap();
+        assert_eq!(recorded["rates"], replayed["rates"]);
+        assert_eq!("REDACTED", replayed["key"]);
+    }
+
+    #[tokio::test]
+    async fn errors_without_secret() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            let body = r#"{"error": "invalid key HIDDEN_KEY"}"#;
+            write!(
+                stream,
+                "HTTP/1.1 401 Unauthorized\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+        let client = HttpClient::live().secret("HIDDEN_KEY");
+        let url = format!("http://{address}/latest?api_key=HIDDEN_KEY");
+        let err = client.get_json(client.get(&url)).await.unwrap_err();
+        server.join().unwrap();
+        let message = format!("{err:?}");
+        assert!(message.contains("invalid key REDACTED"));
+        assert!(!message.contains("HIDDEN_KEY"));
+
+        let closed = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = format!("http://{closed}/latest?api_key=UNREGISTERED_KEY");
+        let err = HttpClient::live()
+            .get_json(client.get(&url))
+            .await
+            .unwrap_err();
+        let message = format!("{err:?}");
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::SendingError
+        ));
+        assert!(message.contains("api_key=REDACTED"));
+        assert!(!message.contains("UNREGISTERED_KEY"));
+    }
+}
@@ -0,0 +1,240 @@
+use crate::connector::{Connector, ConnectorError};
+use crate::connector_output::{ExchangeOutput, LatestOutput};
+use crate::currency::Currency;
+use crate::history::HistoryStore;
+use crate::utility::get_history_db_path;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::{Days, NaiveDate};
+use error_stack::{Report, Result};
+use std::path::Path;
+use std::sync::Mutex;
+
+const END_OF_TIME: &str = "9999-12-31";
+/// Oldest snapshot served by `historical`, counted in days before the requested date
+const MAX_LOOKBACK_DAYS: u64 = 7;
+
+/// Serves exchange rates previously stored with the `record` command
+pub struct LocalHistoryConnector {
+    store: Mutex<HistoryStore>,
+}
+
+impl LocalHistoryConnector {
+    pub fn new() -> Result<Self, ConnectorError> {
+        LocalHistoryConnector::open(&get_history_db_path())
+    }
+    pub fn open(path: &Path) -> Result<Self, ConnectorError> {
+        Ok(LocalHistoryConnector {
+            store: Mutex::new(HistoryStore::open(path)?),
+        })
+    }
+    fn rates(
+        &self,
+        base: &str,
+        after: &str,
+        before: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let rates = self
+            .store
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .snapshot(base, after, before)?;
+        if rates.is_empty() {
+            let msg = format!("No recorded rates for currency code {base}");
+            let mut report = Report::new(ConnectorError::InvalidInput(msg.clone()));
+            if !after.is_empty() {
+                report =
+                    report.attach_printable(format!("Rates recorded before {after} are too old"));
+            }
+            return Err(report.attach_printable(msg));
+        }
+        match target {
+            Some(target) => target
+                .iter()
+                .map(|code| {
+                    rates
+                        .iter()
+                        .find(|latest| latest.get_currency().get_short_code() == code)
+                        .cloned()
+                        .ok_or_else(|| {
+                            let msg = format!("No recorded rate for {base}/{code}");
+                            Report::new(ConnectorError::InvalidInput(msg.clone()))
+                                .attach_printable(msg)
+                        })
+                })
+                .try_collect::<Vec<_>>(),
+            None => Ok(rates),
+        }
+    }
+}
+
+impl Connector for LocalHistoryConnector {
+    fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        let latest = self.rates(source, "", END_OF_TIME, Some(vec![target.to_string()]))?;
+        let rate = latest[0].get_rate();
+        Ok(ExchangeOutput::new(&(amount * rate), rate))
+    }
+
+    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        self.store
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .currencies()
+    }
+
+    fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.rates(base, "", END_OF_TIME, target)
+    }
+
+    fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let before = date
+            .succ_opt()
+            .map_or_else(|| END_OF_TIME.to_string(), |day| day.to_string());
+        let after = date
+            .checked_sub_days(Days::new(MAX_LOOKBACK_DAYS))
+            .map(|day| day.to_string())
+            .unwrap_or_default();
+        self.rates(base, &after, &before, target)
+    }
+}
+
+/// Local database queries are quick, so they run directly on the calling task
+#[async_trait]
+impl crate::async_connector::AsyncConnector for LocalHistoryConnector {
+    async fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        Connector::exchange(self, source, target, amount)
+    }
+
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        Connector::list_currencies(self)
+    }
+
+    async fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        Connector::latest(self, base, target)
+    }
+
+    async fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        Connector::historical(self, date, base, target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connector() -> LocalHistoryConnector {
+        let mut store = HistoryStore::open(Path::new(":memory:")).unwrap();
+        for (day, pln) in [(1, 4), (3, 5)] {
+            let recorded_at = NaiveDate::from_ymd_opt(2026, 1, day)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap();
+            let rates = vec![
+                LatestOutput::new(Currency::new("GBP", None), BigDecimal::from(1)),
+                LatestOutput::new(Currency::new("PLN", None), BigDecimal::from(pln)),
+            ];
+            store.record(recorded_at, "mock", "USD", &rates).unwrap();
+        }
+        LocalHistoryConnector {
+            store: Mutex::new(store),
+        }
+    }
+
+    #[test]
+    fn exchange() {
+        assert_eq!(
+            ExchangeOutput::new(&BigDecimal::from(50), &BigDecimal::from(5)),
+            connector()
+                .exchange("USD", "PLN", &BigDecimal::from(10))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn historical() {
+        let connector = connector();
+        let pln = |date| {
+            connector
+                .historical(date, "USD", Some(vec!["PLN".to_string()]))
+                .unwrap()[0]
+                .get_rate()
+                .clone()
+        };
+        assert_eq!(
+            BigDecimal::from(4),
+            pln(NaiveDate::from_ymd_opt(2026, 1, 2).unwrap())
+        );
+        assert_eq!(
+            BigDecimal::from(5),
+            pln(NaiveDate::from_ymd_opt(2026, 1, 3).unwrap())
+        );
+    }
+
+    #[test]
+    fn historical_before_first_record() {
+        let connector = connector();
+        if let Err(e) =
+            connector.historical(NaiveDate::from_ymd_opt(2025, 12, 31).unwrap(), "USD", None)
+        {
+            if let ConnectorError::InvalidInput(msg) = e.current_context() {
+                assert_eq!("No recorded rates for currency code USD", msg);
+            }
+        } else {
+            panic!("expected error");
+        }
+    }
+
+    #[test]
+    fn historical_lookback_is_bounded() {
+        let connector = connector();
+        let pln = |day| {
+            connector.historical(
+                NaiveDate::from_ymd_opt(2026, 1, day).unwrap(),
+                "USD",
+                Some(vec!["PLN".to_string()]),
+            )
+        };
+        assert_eq!(&BigDecimal::from(5), pln(10).unwrap()[0].get_rate());
+        assert!(pln(11).is_err());
+    }
+
+    #[test]
+    fn latest_target_not_recorded() {
+        if let Err(e) = connector().latest("USD", Some(vec!["EUR".to_string()])) {
+            if let ConnectorError::InvalidInput(msg) = e.current_context() {
+                assert_eq!("No recorded rate for USD/EUR", msg);
+            }
+        } else {
+            panic!("expected error");
+        }
+    }
+}
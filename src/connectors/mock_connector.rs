@@ -1,38 +1,273 @@
 use crate::connector::{Connector, ConnectorError};
 use crate::connector_output::{ExchangeOutput, LatestOutput};
 use crate::currency::Currency;
-use bigdecimal::BigDecimal;
-use error_stack::{Report, Result};
+use async_trait::async_trait;
+use bigdecimal::{BigDecimal, FromPrimitive, Zero};
+use chrono::NaiveDate;
+use error_stack::{Report, Result, ResultExt};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 
+const INJECTED_FAILURE: &str = "Failure injected by mock connector";
+
+/// Offline connector with fixed GBP, PLN and USD exchange rates.
+///
+/// ```
+/// use bigdecimal::BigDecimal;
+/// use exchangeapp::{Connector, MockConnector};
+///
+/// let output = MockConnector::new()
+///     .exchange("GBP", "USD", &BigDecimal::from(8))
+///     .unwrap();
+/// assert_eq!(&BigDecimal::from(10), output.get_value());
+/// ```
+///
+/// Other currencies, latency, rate jitter and failures are set up with [`MockConnector::builder`]
+/// or read from a toml file with [`MockConnector::open`].
 pub struct MockConnector {
     currencies: HashMap<String, Currency>,
     rates: HashMap<String, BigDecimal>,
+    latency: Option<Duration>,
+    jitter: Option<BigDecimal>,
+    failures: Vec<(ConnectorError, f64)>,
+    rng: Mutex<StdRng>,
+}
+
+/// Builder of [`MockConnector`], every rate is relative to the same reference currency.
+///
+/// ```
+/// use bigdecimal::BigDecimal;
+/// use exchangeapp::{Connector, ConnectorError, MockConnector};
+///
+/// let connector = MockConnector::builder()
+///     .currency("USD", Some("US Dollar"), BigDecimal::from(1))
+///     .currency("JPY", None, BigDecimal::from(150))
+///     .failure(ConnectorError::SendingError, 0.5)
+///     .seed(7)
+///     .build();
+/// let outputs = (0..20)
+///     .map(|_| connector.exchange("USD", "JPY", &BigDecimal::from(1)))
+///     .collect::<Vec<_>>();
+/// assert!(outputs.iter().any(Result::is_ok));
+/// assert!(outputs.iter().any(Result::is_err));
+/// ```
+#[derive(Default)]
+#[allow(clippy::module_name_repetitions)]
+pub struct MockConnectorBuilder {
+    currencies: HashMap<String, Currency>,
+    rates: HashMap<String, BigDecimal>,
+    latency: Option<Duration>,
+    jitter: Option<BigDecimal>,
+    failures: Vec<(ConnectorError, f64)>,
+    seed: Option<u64>,
+}
+
+impl MockConnectorBuilder {
+    #[must_use]
+    pub fn currency(mut self, code: &str, name: Option<&str>, rate: BigDecimal) -> Self {
+        let code = code.to_uppercase();
+        self.currencies.insert(
+            code.clone(),
+            Currency::new(&code, name.map(ToString::to_string)),
+        );
+        self.rates.insert(code, rate);
+        self
+    }
+    /// Delay of every request
+    #[must_use]
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = Some(latency);
+        self
+    }
+    /// Change every exchange rate randomly by up to `max` fraction of it, e.g. `0.01` is 1%
+    #[must_use]
+    pub fn jitter(mut self, max: BigDecimal) -> Self {
+        self.jitter = Some(max);
+        self
+    }
+    /// Fail requests with `error` with `probability` between 0 and 1
+    #[must_use]
+    pub fn failure(mut self, error: ConnectorError, probability: f64) -> Self {
+        self.failures.push((error, probability));
+        self
+    }
+    /// Seed of jitter and failures, so they repeat between runs
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    pub fn build(self) -> MockConnector {
+        let rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        MockConnector {
+            currencies: self.currencies,
+            rates: self.rates,
+            latency: self.latency,
+            jitter: self.jitter,
+            failures: self.failures,
+            rng: Mutex::new(rng),
+        }
+    }
+}
+
+/// Error injected for failure name used in mock config file, e.g. `sending_error`
+fn failure_error(name: &str) -> Option<ConnectorError> {
+    match name {
+        "invalid_input" => Some(ConnectorError::InvalidInput(INJECTED_FAILURE.to_string())),
+        "api_key_requirements" => Some(ConnectorError::ApiKeyRequirements),
+        "api_error" => Some(ConnectorError::ApiError),
+        "sending_error" => Some(ConnectorError::SendingError),
+        "json_parsing_error" => Some(ConnectorError::JsonParsingError),
+        "parse_error" => Some(ConnectorError::ParseError),
+        "storage_error" => Some(ConnectorError::StorageError),
+        "file_error" => Some(ConnectorError::FileError),
+        "budget_exceeded" => Some(ConnectorError::BudgetExceeded),
+        "rate_limited" => Some(ConnectorError::RateLimited),
+        _ => None,
+    }
+}
+
+fn invalid_config(msg: String) -> Report<ConnectorError> {
+    Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+}
+
+/// Value of `key` in `table` which must be a non-negative integer
+fn toml_unsigned(table: &toml::Table, key: &str) -> Result<Option<u64>, ConnectorError> {
+    table
+        .get(key)
+        .map(|value| {
+            value
+                .as_integer()
+                .and_then(|value| u64::try_from(value).ok())
+                .ok_or_else(|| {
+                    invalid_config(format!("Invalid {key}, expected a non-negative integer"))
+                })
+        })
+        .transpose()
+}
+
+fn toml_decimal(value: &toml::Value) -> Option<BigDecimal> {
+    match value {
+        toml::Value::String(value) => BigDecimal::from_str(value).ok(),
+        toml::Value::Integer(value) => Some(BigDecimal::from(*value)),
+        toml::Value::Float(value) => BigDecimal::from_f64(*value),
+        _ => None,
+    }
 }
 
 impl MockConnector {
     pub fn new() -> Self {
-        let currencies = HashMap::from([
-            (
-                "GBP".to_string(),
-                Currency::new("GBP", Some("Pound Sterling".to_string())),
-            ),
-            (
-                "PLN".to_string(),
-                Currency::new("PLN", Some("Zloty".to_string())),
-            ),
-            (
-                "USD".to_string(),
-                Currency::new("USD", Some("US Dollar".to_string())),
-            ),
-        ]);
-        let rates = HashMap::from([
-            ("GBP".to_string(), BigDecimal::from_str("0.8").unwrap()),
-            ("USD".to_string(), BigDecimal::from_str("1").unwrap()),
-            ("PLN".to_string(), BigDecimal::from_str("4").unwrap()),
-        ]);
-        MockConnector { currencies, rates }
+        MockConnector::builder()
+            .currency("GBP", Some("Pound Sterling"), BigDecimal::new(8.into(), 1))
+            .currency("PLN", Some("Zloty"), BigDecimal::from(4))
+            .currency("USD", Some("US Dollar"), BigDecimal::from(1))
+            .build()
+    }
+    pub fn builder() -> MockConnectorBuilder {
+        MockConnectorBuilder::default()
+    }
+    /// Read mock from a toml file, e.g.
+    ///
+    /// ```toml
+    /// seed = 42
+    /// latency_ms = 200
+    /// jitter = "0.01"
+    ///
+    /// [currencies]
+    /// USD = { name = "US Dollar", rate = 1 }
+    /// JPY = 151.2
+    ///
+    /// [failures]
+    /// sending_error = 0.05
+    /// api_error = 0.01
+    /// ```
+    pub fn open(path: &Path) -> Result<Self, ConnectorError> {
+        let text = fs::read_to_string(path)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+        MockConnector::from_toml(&text)
+            .attach_printable_lazy(|| format!("Error in mock config {}", path.display()))
+    }
+    #[allow(clippy::cast_precision_loss)]
+    fn from_toml(text: &str) -> Result<Self, ConnectorError> {
+        let table = text
+            .parse::<toml::Table>()
+            .change_context(ConnectorError::ParseError)?;
+        let mut builder = MockConnector::builder();
+        let currencies = table.get("currencies").and_then(toml::Value::as_table);
+        for (code, currency) in currencies.into_iter().flatten() {
+            let name = currency.get("name").and_then(toml::Value::as_str);
+            let rate = currency
+                .get("rate")
+                .map_or_else(|| toml_decimal(currency), toml_decimal)
+                .ok_or_else(|| invalid_config(format!("Invalid rate of {code}")))?;
+            builder = builder.currency(code, name, rate);
+        }
+        let failures = table.get("failures").and_then(toml::Value::as_table);
+        for (name, probability) in failures.into_iter().flatten() {
+            let error = failure_error(name)
+                .ok_or_else(|| invalid_config(format!("Unknown failure {name}")))?;
+            let probability = probability
+                .as_float()
+                .or_else(|| probability.as_integer().map(|value| value as f64))
+                .filter(|probability| (0.0..=1.0).contains(probability))
+                .ok_or_else(|| invalid_config(format!("Invalid probability of {name}")))?;
+            builder = builder.failure(error, probability);
+        }
+        if let Some(latency) = toml_unsigned(&table, "latency_ms")? {
+            builder = builder.latency(Duration::from_millis(latency));
+        }
+        if let Some(jitter) = table.get("jitter") {
+            builder = builder.jitter(
+                toml_decimal(jitter).ok_or_else(|| invalid_config("Invalid jitter".to_string()))?,
+            );
+        }
+        if let Some(seed) = toml_unsigned(&table, "seed")? {
+            builder = builder.seed(seed);
+        }
+        Ok(builder.build())
+    }
+    fn rng(&self) -> std::sync::MutexGuard<'_, StdRng> {
+        self.rng
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+    /// Fail with one of configured errors according to their probabilities
+    fn inject_failure(&self) -> Result<(), ConnectorError> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        let roll = self.rng().gen::<f64>();
+        let mut threshold = 0.0;
+        for (error, probability) in &self.failures {
+            threshold += probability;
+            if roll < threshold {
+                return Err(Report::new(error.clone()).attach_printable(INJECTED_FAILURE));
+            }
+        }
+        Ok(())
+    }
+    fn before_request(&self) -> Result<(), ConnectorError> {
+        if let Some(latency) = self.latency {
+            thread::sleep(latency);
+        }
+        self.inject_failure()
+    }
+    async fn before_request_async(&self) -> Result<(), ConnectorError> {
+        if let Some(latency) = self.latency {
+            tokio::time::sleep(latency).await;
+        }
+        self.inject_failure()
     }
     fn rate(&self, source: &str, target: &str) -> Result<BigDecimal, ConnectorError> {
         let source_rate = self.rates.get(source).ok_or_else(|| {
@@ -43,7 +278,22 @@ impl MockConnector {
             let msg = format!("Target currency code {target} not exist");
             Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
         })?;
-        Ok(target_rate / source_rate)
+        if source_rate.is_zero() {
+            let msg = format!("Source currency code {source} has zero rate");
+            return Err(
+                Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+            );
+        }
+        let rate = target_rate / source_rate;
+        match &self.jitter {
+            Some(jitter) if source != target => {
+                let change = BigDecimal::from_f64(self.rng().gen_range(-1.0..=1.0))
+                    .unwrap_or_default()
+                    * jitter;
+                Ok((&rate + &rate * change).round(rate.fractional_digit_count().max(6)))
+            }
+            _ => Ok(rate),
+        }
     }
     fn get_currency(&self, currency_str: &str) -> Result<Currency, ConnectorError> {
         let currency = self.currencies.get(currency_str);
@@ -54,10 +304,7 @@ impl MockConnector {
             Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg))
         }
     }
-}
-
-impl Connector for MockConnector {
-    fn exchange(
+    fn exchange_now(
         &self,
         source: &str,
         target: &str,
@@ -69,14 +316,12 @@ impl Connector for MockConnector {
             exchange_rate,
         ))
     }
-
-    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+    fn sorted_currencies(&self) -> Vec<Currency> {
         let mut currencies = self.currencies.values().cloned().collect::<Vec<_>>();
         currencies.sort_unstable();
-        Ok(currencies)
+        currencies
     }
-
-    fn latest(
+    fn latest_now(
         &self,
         base: &str,
         target: Option<Vec<String>>,
@@ -92,16 +337,101 @@ impl Connector for MockConnector {
                 .iter()
                 .map(|c| self.get_currency(c))
                 .try_collect::<Vec<_>>()?,
-            None => self.list_currencies().unwrap(),
+            None => self.sorted_currencies(),
         }
         .iter()
-        .map(|c| LatestOutput::new(c.clone(), self.rate(base, c.get_short_code()).unwrap()))
-        .collect::<Vec<_>>();
+        .map(|c| {
+            Ok::<_, Report<ConnectorError>>(LatestOutput::new(
+                c.clone(),
+                self.rate(base, c.get_short_code())?,
+            ))
+        })
+        .try_collect::<Vec<_>>()?;
         currencies.sort_unstable();
         Ok(currencies)
     }
 }
 
+impl Default for MockConnector {
+    fn default() -> Self {
+        MockConnector::new()
+    }
+}
+
+impl Connector for MockConnector {
+    fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        self.before_request()?;
+        self.exchange_now(source, target, amount)
+    }
+
+    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        self.before_request()?;
+        Ok(self.sorted_currencies())
+    }
+
+    fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.before_request()?;
+        self.latest_now(base, target)
+    }
+
+    fn historical(
+        &self,
+        _date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.before_request()?;
+        self.latest_now(base, target)
+    }
+}
+
+/// Latency is simulated without blocking the runtime
+#[async_trait]
+impl crate::async_connector::AsyncConnector for MockConnector {
+    async fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        self.before_request_async().await?;
+        self.exchange_now(source, target, amount)
+    }
+
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        self.before_request_async().await?;
+        Ok(self.sorted_currencies())
+    }
+
+    async fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.before_request_async().await?;
+        self.latest_now(base, target)
+    }
+
+    async fn historical(
+        &self,
+        _date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.before_request_async().await?;
+        self.latest_now(base, target)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -167,4 +497,64 @@ mod tests {
                 .unwrap()
         );
     }
+
+    #[test]
+    fn config_file() {
+        let connector = MockConnector::from_toml(
+            "[currencies]\n\
+             USD = { name = \"US Dollar\", rate = 1 }\n\
+             JPY = \"150\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            ExchangeOutput::new(&BigDecimal::from(300), &BigDecimal::from(150)),
+            connector
+                .exchange("USD", "JPY", &BigDecimal::from(2))
+                .unwrap()
+        );
+        assert!(MockConnector::from_toml("[failures]\nunknown = 0.5\n").is_err());
+        for config in [
+            "latency_ms = -1\n",
+            "seed = -42\n",
+            "[failures]\napi_error = 1.5\n",
+            "[failures]\napi_error = -0.1\n",
+        ] {
+            let err = MockConnector::from_toml(config).err().unwrap();
+            assert!(matches!(
+                err.current_context(),
+                ConnectorError::InvalidInput(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn seeded_jitter() {
+        let rates = || {
+            let connector = MockConnector::builder()
+                .currency("USD", None, BigDecimal::from(1))
+                .currency("PLN", None, BigDecimal::from(4))
+                .jitter(BigDecimal::from_str("0.1").unwrap())
+                .seed(1)
+                .build();
+            (0..5)
+                .map(|_| connector.rate("USD", "PLN").unwrap())
+                .collect::<Vec<_>>()
+        };
+        let first = rates();
+        assert_eq!(first, rates());
+        assert!(first
+            .iter()
+            .all(|rate| *rate >= BigDecimal::from_str("3.6").unwrap()
+                && *rate <= BigDecimal::from_str("4.4").unwrap()));
+    }
+
+    #[test]
+    fn injected_failure() {
+        let connector = MockConnector::builder()
+            .currency("USD", None, BigDecimal::from(1))
+            .failure(ConnectorError::ApiError, 1.0)
+            .build();
+        let err = connector.list_currencies().unwrap_err();
+        assert!(matches!(err.current_context(), ConnectorError::ApiError));
+    }
 }
@@ -1,2 +1,17 @@
+#[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+pub mod api_keys;
+#[cfg(feature = "currencybeacon")]
 pub mod currencybeacon_connector;
+#[cfg(feature = "file")]
+pub mod file_connector;
+#[cfg(feature = "generic-rest")]
+pub mod generic_rest_connector;
+#[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+pub mod http;
+#[cfg(feature = "local-history")]
+pub mod local_history_connector;
 pub mod mock_connector;
+#[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+pub mod rate_limit;
+#[cfg(any(feature = "currencybeacon", feature = "generic-rest"))]
+mod response;
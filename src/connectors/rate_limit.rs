@@ -0,0 +1,234 @@
+use crate::connector::{ConnectorEnum, ConnectorError};
+use crate::state_file::{self, write_atomic};
+use crate::utility::{
+    connector_name, get_rate_limit, get_rate_limit_mode, get_rate_limit_path, parse_duration,
+};
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use strum_macros::{Display, EnumString};
+
+/// What [`RateLimiter`] does when no request is available
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum RateLimitMode {
+    /// Wait until the next request is available
+    #[default]
+    Wait,
+    /// Fail with [`ConnectorError::RateLimited`]
+    Fail,
+}
+
+/// Number of requests allowed per interval, e.g. `10/1m`; all of them can be sent at once
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    requests: u32,
+    interval: Duration,
+}
+
+impl RateLimit {
+    pub fn new(requests: u32, interval: Duration) -> Self {
+        RateLimit { requests, interval }
+    }
+    fn per_second(&self) -> f64 {
+        f64::from(self.requests) / self.interval.as_secs_f64()
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+
+    fn from_str(limit: &str) -> std::result::Result<Self, Self::Err> {
+        let invalid = || format!("Invalid rate limit {limit}, expected e.g. 10/1m");
+        let (requests, interval) = limit.split_once('/').ok_or_else(invalid)?;
+        match requests.trim().parse::<u32>() {
+            Ok(requests) if requests > 0 => Ok(RateLimit {
+                requests,
+                interval: parse_duration(interval.trim()).map_err(|_| invalid())?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Token bucket of a connector shared by all processes through a state file,
+/// guarded by an advisory lock on a file next to it.
+///
+/// Limit is read from `EAPP_RATE_LIMIT_<CONNECTOR>`, e.g. `EAPP_RATE_LIMIT_CURRENCYBEACON=10/1m`,
+/// mode from `EAPP_RATE_LIMIT_MODE` (`wait` or `fail`) and state file from `EAPP_RATE_LIMIT_FILE`.
+#[derive(Clone)]
+pub struct RateLimiter {
+    connector: String,
+    path: PathBuf,
+    limit: RateLimit,
+    mode: RateLimitMode,
+}
+
+impl RateLimiter {
+    /// Limiter of `connector`, `None` when it has no rate limit set
+    pub fn new(connector: ConnectorEnum) -> Result<Option<Self>, ConnectorError> {
+        let Some(limit) = get_rate_limit(connector) else {
+            return Ok(None);
+        };
+        let limit = RateLimit::from_str(&limit)
+            .map_err(|err| Report::new(ConnectorError::InvalidInput(err)))
+            .attach_printable_lazy(|| {
+                format!("Invalid rate limit of {}", connector_name(connector))
+            })?;
+        let mode = get_rate_limit_mode();
+        let mode = RateLimitMode::from_str(&mode)
+            .change_context_lazy(|| {
+                ConnectorError::InvalidInput(format!("Invalid rate limit mode {mode}"))
+            })
+            .attach_printable("Expected wait or fail")?;
+        Ok(Some(
+            RateLimiter::with_limit(&connector_name(connector), &get_rate_limit_path(), limit)
+                .mode(mode),
+        ))
+    }
+    /// Limiter of `connector` keeping its bucket in state file at `path`
+    pub fn with_limit(connector: &str, path: &Path, limit: RateLimit) -> Self {
+        RateLimiter {
+            connector: connector.to_string(),
+            path: path.to_path_buf(),
+            limit,
+            mode: RateLimitMode::default(),
+        }
+    }
+    #[must_use]
+    pub fn mode(mut self, mode: RateLimitMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// Take a request from the bucket, waiting for it or failing depending on the mode
+    pub async fn acquire(&self) -> Result<(), ConnectorError> {
+        loop {
+            let Some(wait) = self.try_acquire(SystemTime::now()).await? else {
+                return Ok(());
+            };
+            if self.mode == RateLimitMode::Fail {
+                return Err(
+                    Report::new(ConnectorError::RateLimited).attach_printable(format!(
+                        "Rate limit of {} reached, next request in {:.1}s",
+                        self.connector,
+                        wait.as_secs_f64()
+                    )),
+                );
+            }
+            tracing::debug!(
+                "Rate limit of {} reached, waiting {:.1}s",
+                self.connector,
+                wait.as_secs_f64()
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+    /// Take a request from the bucket at `now`, otherwise time until the next one is available
+    async fn try_acquire(&self, now: SystemTime) -> Result<Option<Duration>, ConnectorError> {
+        let limiter = self.clone();
+        state_file::update(&self.path, move || limiter.take(now)).await
+    }
+    /// Update the bucket at `now`, the caller holds the lock of the state file
+    fn take(&self, now: SystemTime) -> Result<Option<Duration>, ConnectorError> {
+        let mut state = load_state(&self.path)?;
+        let now = now
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let capacity = f64::from(self.limit.requests);
+        let bucket = &state[self.connector.as_str()];
+        let tokens = match (bucket["tokens"].as_f64(), bucket["updated"].as_f64()) {
+            (Some(tokens), Some(updated)) => {
+                (tokens + (now - updated).max(0.0) * self.limit.per_second()).min(capacity)
+            }
+            _ => capacity,
+        };
+        let (tokens, wait) = if tokens >= 1.0 {
+            (tokens - 1.0, None)
+        } else {
+            let wait = (1.0 - tokens) / self.limit.per_second();
+            (tokens, Some(Duration::from_secs_f64(wait)))
+        };
+        state[self.connector.as_str()] = json::object! {tokens: tokens, updated: now};
+        write_atomic(&self.path, &state.pretty(2))?;
+        Ok(wait)
+    }
+}
+
+/// State is a json object of `{"tokens", "updated"}` buckets by connector name
+fn load_state(path: &Path) -> Result<JsonValue, ConnectorError> {
+    if !path.exists() {
+        return Ok(JsonValue::new_object());
+    }
+    let text = fs::read_to_string(path)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+    match json::parse(&text) {
+        Ok(state) if state.is_object() => Ok(state),
+        _ => {
+            tracing::warn!("Resetting invalid rate limit state {}", path.display());
+            Ok(JsonValue::new_object())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn limiter(dir: &TempDir, limit: &str) -> RateLimiter {
+        let path = dir.path().join("rate_limit.json");
+        RateLimiter::with_limit("mock", &path, RateLimit::from_str(limit).unwrap())
+    }
+
+    #[test]
+    fn rate_limits() {
+        assert_eq!(
+            Ok(RateLimit::new(10, Duration::from_secs(60))),
+            RateLimit::from_str("10/1m")
+        );
+        assert!(RateLimit::from_str("10").is_err());
+        assert!(RateLimit::from_str("0/1s").is_err());
+    }
+
+    #[tokio::test]
+    async fn bucket_refills_over_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let limiter = limiter(&dir, "2/10s");
+        let start = SystemTime::now();
+        assert_eq!(None, limiter.try_acquire(start).await.unwrap());
+        assert_eq!(None, limiter.try_acquire(start).await.unwrap());
+        let wait = limiter.try_acquire(start).await.unwrap().unwrap();
+        assert!(wait <= Duration::from_secs(5) && wait > Duration::from_secs(4));
+        assert_eq!(
+            None,
+            limiter
+                .try_acquire(start + Duration::from_secs(6))
+                .await
+                .unwrap()
+        );
+        let lock = state_file::lock_path(&limiter.path);
+        assert!(state_file::FileLock::acquire_async(&lock).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn concurrent_requests_share_bucket() {
+        let dir = tempfile::tempdir().unwrap();
+        let limiter = limiter(&dir, "5/1h").mode(RateLimitMode::Fail);
+        let results = futures::future::join_all((0..8).map(|_| limiter.acquire())).await;
+        assert_eq!(5, results.iter().filter(|result| result.is_ok()).count());
+    }
+
+    #[tokio::test]
+    async fn fail_mode() {
+        let dir = tempfile::tempdir().unwrap();
+        let limiter = limiter(&dir, "1/1h").mode(RateLimitMode::Fail);
+        limiter.acquire().await.unwrap();
+        let err = limiter.acquire().await.unwrap_err();
+        assert!(matches!(err.current_context(), ConnectorError::RateLimited));
+    }
+}
@@ -0,0 +1,239 @@
+use crate::connector::ConnectorError;
+use crate::connector_output::LatestOutput;
+use crate::currency::Currency;
+use bigdecimal::{BigDecimal, Signed};
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::str::FromStr;
+
+fn parse_error(msg: String) -> Report<ConnectorError> {
+    Report::new(ConnectorError::ParseError).attach_printable(msg)
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() {
+        "<root>"
+    } else {
+        path
+    }
+}
+
+/// Value at dotted `path`, e.g. `response.rates` or `data.0.code`; empty path is the whole json
+pub(crate) fn lookup<'a>(json: &'a JsonValue, path: &str) -> &'a JsonValue {
+    path.split('.')
+        .filter(|key| !key.is_empty())
+        .fold(json, |json, key| match key.parse::<usize>() {
+            Ok(index) if json.is_array() => &json[index],
+            _ => &json[key],
+        })
+}
+
+/// Value at dotted `path` which must be present
+pub(crate) fn at_path<'a>(
+    json: &'a JsonValue,
+    path: &str,
+) -> Result<&'a JsonValue, ConnectorError> {
+    let value = lookup(json, path);
+    if value.is_null() {
+        return Err(parse_error(format!("Missing field {}", display_path(path))));
+    }
+    Ok(value)
+}
+
+/// Exchange rate at `path`, a json number or numeric string greater than zero
+pub(crate) fn parse_rate(value: &JsonValue, path: &str) -> Result<BigDecimal, ConnectorError> {
+    let text = if value.is_number() {
+        value.dump()
+    } else if let Some(text) = value.as_str() {
+        text.trim().to_string()
+    } else {
+        return Err(parse_error(format!(
+            "Rate at {path} is not a number: {}",
+            value.dump()
+        )));
+    };
+    let rate = BigDecimal::from_str(&text)
+        .change_context(ConnectorError::ParseError)
+        .attach_printable_lazy(|| format!("Rate at {path} is not a number: {text}"))?;
+    if !rate.is_positive() {
+        return Err(parse_error(format!(
+            "Rate at {path} is not positive: {rate}"
+        )));
+    }
+    Ok(rate)
+}
+
+fn parse_code(code: &str, path: &str) -> Result<String, ConnectorError> {
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(parse_error(format!(
+            "Invalid currency code at {path}: {code:?}"
+        )));
+    }
+    Ok(code.to_uppercase())
+}
+
+fn optional_string(value: &JsonValue, path: &str) -> Result<Option<String>, ConnectorError> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    value
+        .as_str()
+        .map(|text| Some(text.to_string()))
+        .ok_or_else(|| parse_error(format!("Field {path} is not a string: {}", value.dump())))
+}
+
+/// Map of currency codes and rates at `path`
+pub(crate) fn parse_rates(
+    json: &JsonValue,
+    path: &str,
+) -> Result<Vec<LatestOutput>, ConnectorError> {
+    let rates = at_path(json, path)?;
+    if !rates.is_object() {
+        return Err(parse_error(format!(
+            "Field {} is not an object: {}",
+            display_path(path),
+            rates.dump()
+        )));
+    }
+    rates
+        .entries()
+        .map(|(code, rate)| {
+            let field = child_path(path, code);
+            let code = parse_code(code, &field)?;
+            Ok(LatestOutput::new(
+                Currency::new(&code, None),
+                parse_rate(rate, &field)?,
+            ))
+        })
+        .try_collect::<Vec<_>>()
+}
+
+/// List of objects with `code` and optional `name` fields at `path`, or map of codes and names
+pub(crate) fn parse_currencies(
+    json: &JsonValue,
+    path: &str,
+    code: &str,
+    name: &str,
+) -> Result<Vec<Currency>, ConnectorError> {
+    let currencies = at_path(json, path)?;
+    if currencies.is_array() {
+        currencies
+            .members()
+            .enumerate()
+            .map(|(i, currency)| {
+                let item = child_path(path, &i.to_string());
+                let code_path = child_path(&item, code);
+                let short_code = optional_string(&currency[code], &code_path)?
+                    .ok_or_else(|| parse_error(format!("Missing field {code_path}")))?;
+                Ok(Currency::new(
+                    &parse_code(&short_code, &code_path)?,
+                    optional_string(&currency[name], &child_path(&item, name))?,
+                ))
+            })
+            .try_collect::<Vec<_>>()
+    } else if currencies.is_object() {
+        currencies
+            .entries()
+            .map(|(code, name)| {
+                let field = child_path(path, code);
+                Ok(Currency::new(
+                    &parse_code(code, &field)?,
+                    optional_string(name, &field)?,
+                ))
+            })
+            .try_collect::<Vec<_>>()
+    } else {
+        Err(parse_error(format!(
+            "Field {} is not a list or an object: {}",
+            display_path(path),
+            currencies.dump()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn error_message(json: &JsonValue) -> String {
+        format!("{:?}", parse_rates(json, "response.rates").unwrap_err())
+    }
+
+    #[test]
+    fn field_path_in_errors() {
+        assert!(error_message(&json::object! {response: {}}).contains("response.rates"));
+        assert!(error_message(&json::object! {response: {rates: {PLN: -4}}})
+            .contains("response.rates.PLN"));
+        assert!(
+            error_message(&json::object! {response: {rates: {PLN: "four"}}})
+                .contains("response.rates.PLN")
+        );
+        assert!(error_message(&json::object! {response: {rates: [4]}}).contains("not an object"));
+        let currencies = json::array![{short_code: "USD"}, {name: "Euro"}];
+        let err = parse_currencies(&currencies, "", "short_code", "name").unwrap_err();
+        assert!(format!("{err:?}").contains("1.short_code"));
+    }
+
+    #[test]
+    fn numeric_strings_and_numbers() {
+        let rates = parse_rates(&json::object! {PLN: "3.98", EUR: 0.92}, "").unwrap();
+        assert_eq!(
+            vec![
+                LatestOutput::new(
+                    Currency::new("PLN", None),
+                    BigDecimal::from_str("3.98").unwrap()
+                ),
+                LatestOutput::new(
+                    Currency::new("EUR", None),
+                    BigDecimal::from_str("0.92").unwrap()
+                ),
+            ],
+            rates
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn any_json_never_panics(text in "\\PC*") {
+            if let Ok(json) = json::parse(&text) {
+                let _ = parse_rates(&json, "rates");
+                let _ = parse_currencies(&json, "response", "short_code", "name");
+            }
+        }
+
+        #[test]
+        fn positive_rates_accepted(rate in 1e-9f64..1e9) {
+            let rates = parse_rates(&json::object! {rates: {PLN: rate}}, "rates").unwrap();
+            prop_assert!(rates[0].get_rate().is_positive());
+        }
+
+        #[test]
+        fn non_positive_rates_rejected(rate in -1e9f64..=0.0) {
+            let json = json::object! {rates: {PLN: rate}};
+            let err = parse_rates(&json, "rates").unwrap_err();
+            prop_assert!(matches!(err.current_context(), ConnectorError::ParseError));
+            let message = format!("{err:?}");
+            prop_assert!(message.contains("rates.PLN"));
+        }
+
+        #[test]
+        fn any_string_rate_is_positive_or_error(text in "\\PC*") {
+            match parse_rates(&json::object! {rates: {PLN: text.as_str()}}, "rates") {
+                Ok(rates) => prop_assert!(rates[0].get_rate().is_positive()),
+                Err(err) => {
+                    let message = format!("{err:?}");
+                    prop_assert!(message.contains("rates.PLN"));
+                }
+            }
+        }
+    }
+}
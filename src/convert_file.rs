@@ -0,0 +1,388 @@
+use crate::connector::{Connector, ConnectorError};
+use crate::metrics;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const CONVERTED_COLUMN: &str = "converted_amount";
+const RATE_COLUMN: &str = "exchange_rate";
+
+/// Exchange rates to the target currency, fetched once per currency and date
+pub struct RateCache<'a> {
+    connector: &'a dyn Connector,
+    target: String,
+    rates: HashMap<(String, Option<NaiveDate>), BigDecimal>,
+    requests: usize,
+}
+
+impl<'a> RateCache<'a> {
+    pub fn new(connector: &'a dyn Connector, target: &str) -> Self {
+        RateCache {
+            connector,
+            target: target.to_uppercase(),
+            rates: HashMap::new(),
+            requests: 0,
+        }
+    }
+
+    fn rate(
+        &mut self,
+        currency: &str,
+        date: Option<NaiveDate>,
+    ) -> Result<BigDecimal, ConnectorError> {
+        if currency == self.target {
+            return Ok(BigDecimal::from(1));
+        }
+        let key = (currency.to_string(), date);
+        if let Some(rate) = self.rates.get(&key) {
+            tracing::trace!(currency, ?date, cache = "hit", "Exchange rate");
+            metrics::observe_cache("convert-file", true);
+            return Ok(rate.clone());
+        }
+        tracing::debug!(currency, ?date, cache = "miss", "Exchange rate");
+        metrics::observe_cache("convert-file", false);
+        let target = Some(vec![self.target.clone()]);
+        self.requests += 1;
+        let rates = match date {
+            Some(date) => self.connector.historical(date, currency, target)?,
+            None => self.connector.latest(currency, target)?,
+        };
+        let rate = rates
+            .iter()
+            .find(|latest| latest.get_currency().get_short_code() == self.target)
+            .map(|latest| latest.get_rate().clone())
+            .ok_or_else(|| {
+                let msg = format!("No exchange rate for {currency}/{}", self.target);
+                Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+            })?;
+        self.rates.insert(key, rate.clone());
+        Ok(rate)
+    }
+
+    /// Converted amount and exchange rate of one row
+    fn convert(
+        &mut self,
+        amount: &str,
+        currency: &str,
+        date: Option<&str>,
+    ) -> Result<(BigDecimal, BigDecimal), ConnectorError> {
+        let amount = BigDecimal::from_str(amount.trim()).change_context_lazy(|| {
+            ConnectorError::InvalidInput(format!("Invalid amount {amount}"))
+        })?;
+        let date = match date.map(str::trim).filter(|date| !date.is_empty()) {
+            Some(date) => Some(NaiveDate::from_str(date).change_context_lazy(|| {
+                ConnectorError::InvalidInput(format!("Invalid date {date}"))
+            })?),
+            None => None,
+        };
+        let rate = self.rate(&currency.trim().to_uppercase(), date)?;
+        Ok((amount * &rate, rate))
+    }
+}
+
+/// Names of the columns read from every row
+pub struct Columns {
+    pub amount: String,
+    pub currency: String,
+    pub date: Option<String>,
+}
+
+pub struct ConvertSummary {
+    rows: usize,
+    requests: usize,
+    output: PathBuf,
+}
+
+impl fmt::Display for ConvertSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Converted {} rows with {} exchange rate requests into {}",
+            self.rows,
+            self.requests,
+            self.output.display()
+        )
+    }
+}
+
+/// Convert `input` into `output`, format is chosen by extension of `input`: `csv`, `json` or `jsonl`
+pub fn convert_file(
+    input: &Path,
+    output: &Path,
+    columns: &Columns,
+    cache: &mut RateCache,
+) -> Result<ConvertSummary, ConnectorError> {
+    if let (Ok(input), Ok(output)) = (fs::canonicalize(input), fs::canonicalize(output)) {
+        if input == output {
+            let msg = format!("Output {} is the input file", output.display());
+            return Err(
+                Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+            );
+        }
+    }
+    let extension = input
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+    let rows = match extension.as_deref() {
+        Some("csv") => convert_csv(input, output, columns, cache),
+        Some("json") => convert_json(input, output, columns, cache),
+        Some("jsonl" | "ndjson") => convert_json_lines(input, output, columns, cache),
+        _ => {
+            let msg = format!(
+                "Unsupported file {}, expected csv, json or jsonl",
+                input.display()
+            );
+            Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg))
+        }
+    }?;
+    Ok(ConvertSummary {
+        rows,
+        requests: cache.requests,
+        output: output.to_path_buf(),
+    })
+}
+
+fn convert_csv(
+    input: &Path,
+    output: &Path,
+    columns: &Columns,
+    cache: &mut RateCache,
+) -> Result<usize, ConnectorError> {
+    let mut reader = csv::Reader::from_path(input)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", input.display()))?;
+    let mut headers = reader
+        .headers()
+        .change_context(ConnectorError::FileError)?
+        .clone();
+    let index = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| {
+                let msg = format!("Column {name} not exist");
+                Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+            })
+    };
+    let amount_index = index(columns.amount.as_str())?;
+    let currency_index = index(columns.currency.as_str())?;
+    let date_index = columns.date.as_deref().map(index).transpose()?;
+
+    let mut writer = csv::Writer::from_path(output)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with writing {}", output.display()))?;
+    headers.push_field(CONVERTED_COLUMN);
+    headers.push_field(RATE_COLUMN);
+    writer
+        .write_record(&headers)
+        .change_context(ConnectorError::FileError)?;
+    let mut rows = 0;
+    for record in reader.records() {
+        rows += 1;
+        let mut record = record.change_context(ConnectorError::FileError)?;
+        let (converted, rate) = cache
+            .convert(
+                record.get(amount_index).unwrap_or_default(),
+                record.get(currency_index).unwrap_or_default(),
+                date_index.and_then(|index| record.get(index)),
+            )
+            .attach_printable_lazy(|| format!("Error in row {rows}"))?;
+        record.push_field(&converted.to_string());
+        record.push_field(&rate.to_string());
+        writer
+            .write_record(&record)
+            .change_context(ConnectorError::FileError)?;
+    }
+    writer.flush().change_context(ConnectorError::FileError)?;
+    Ok(rows)
+}
+
+fn convert_json_row(
+    row: &mut JsonValue,
+    columns: &Columns,
+    cache: &mut RateCache,
+) -> Result<(), ConnectorError> {
+    if !row.is_object() {
+        let msg = "Row is not a json object".to_string();
+        return Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg));
+    }
+    let date = columns
+        .date
+        .as_ref()
+        .map(|date| &row[date.as_str()])
+        .filter(|date| !date.is_null())
+        .map(ToString::to_string);
+    let (converted, rate) = cache.convert(
+        &row[columns.amount.as_str()].to_string(),
+        &row[columns.currency.as_str()].to_string(),
+        date.as_deref(),
+    )?;
+    row[CONVERTED_COLUMN] = converted.to_string().into();
+    row[RATE_COLUMN] = rate.to_string().into();
+    Ok(())
+}
+
+fn convert_json(
+    input: &Path,
+    output: &Path,
+    columns: &Columns,
+    cache: &mut RateCache,
+) -> Result<usize, ConnectorError> {
+    let text = fs::read_to_string(input)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", input.display()))?;
+    let mut json = json::parse(&text).change_context(ConnectorError::JsonParsingError)?;
+    if !json.is_array() {
+        let msg = format!("Root of {} is not a json array", input.display());
+        return Err(Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg));
+    }
+    let mut rows = 0;
+    for row in json.members_mut() {
+        rows += 1;
+        convert_json_row(row, columns, cache)
+            .attach_printable_lazy(|| format!("Error in row {rows}"))?;
+    }
+    fs::write(output, json.pretty(2))
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with writing {}", output.display()))?;
+    Ok(rows)
+}
+
+fn convert_json_lines(
+    input: &Path,
+    output: &Path,
+    columns: &Columns,
+    cache: &mut RateCache,
+) -> Result<usize, ConnectorError> {
+    let reader = File::open(input)
+        .map(BufReader::new)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", input.display()))?;
+    let mut writer = File::create(output)
+        .map(BufWriter::new)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with writing {}", output.display()))?;
+    let mut rows = 0;
+    for line in reader.lines() {
+        let line = line.change_context(ConnectorError::FileError)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        rows += 1;
+        let mut row = json::parse(&line)
+            .change_context(ConnectorError::JsonParsingError)
+            .attach_printable_lazy(|| format!("Error in row {rows}"))?;
+        convert_json_row(&mut row, columns, cache)
+            .attach_printable_lazy(|| format!("Error in row {rows}"))?;
+        writeln!(writer, "{}", row.dump()).change_context(ConnectorError::FileError)?;
+    }
+    writer.flush().change_context(ConnectorError::FileError)?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::mock_connector::MockConnector;
+
+    fn columns() -> Columns {
+        Columns {
+            amount: "amount".to_string(),
+            currency: "currency".to_string(),
+            date: Some("date".to_string()),
+        }
+    }
+
+    fn try_convert(name: &str, content: &str) -> Result<(String, usize), ConnectorError> {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join(name);
+        let output = dir.path().join(format!("converted-{name}"));
+        fs::write(&input, content).unwrap();
+        let connector = MockConnector::new();
+        let mut cache = RateCache::new(&connector, "PLN");
+        let summary = convert_file(&input, &output, &columns(), &mut cache);
+        let converted = fs::read_to_string(&output).unwrap_or_default();
+        Ok((converted, summary?.requests))
+    }
+
+    fn convert(name: &str, content: &str) -> (String, usize) {
+        try_convert(name, content).unwrap()
+    }
+
+    #[test]
+    fn csv_one_request_per_currency_and_date() {
+        let (converted, requests) = convert(
+            "input.csv",
+            "date,currency,amount\n\
+             2026-01-01,USD,10\n\
+             2026-01-01,usd,5\n\
+             ,GBP,8\n\
+             2026-01-02,PLN,3\n",
+        );
+        assert_eq!(
+            "date,currency,amount,converted_amount,exchange_rate\n\
+             2026-01-01,USD,10,40,4\n\
+             2026-01-01,usd,5,20,4\n\
+             ,GBP,8,40,5\n\
+             2026-01-02,PLN,3,3,1\n",
+            converted
+        );
+        assert_eq!(2, requests);
+    }
+
+    #[test]
+    fn json_lines() {
+        let (converted, requests) = convert(
+            "input.jsonl",
+            "{\"currency\":\"USD\",\"amount\":10}\n{\"currency\":\"USD\",\"amount\":\"2.5\"}\n",
+        );
+        assert_eq!(
+            "{\"currency\":\"USD\",\"amount\":10,\"converted_amount\":\"40\",\"exchange_rate\":\"4\"}\n\
+             {\"currency\":\"USD\",\"amount\":\"2.5\",\"converted_amount\":\"10.0\",\"exchange_rate\":\"4\"}\n",
+            converted
+        );
+        assert_eq!(1, requests);
+    }
+
+    #[test]
+    fn json_root_must_be_array() {
+        let err = try_convert("object.json", r#"{"currency": "USD", "amount": 10}"#).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::InvalidInput(_)
+        ));
+    }
+
+    #[test]
+    fn output_must_differ_from_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("same.csv");
+        fs::write(&input, "currency,amount\nUSD,10\n").unwrap();
+        let connector = MockConnector::new();
+        let mut cache = RateCache::new(&connector, "PLN");
+        let result = convert_file(
+            &input,
+            &dir.path().join(".").join("same.csv"),
+            &columns(),
+            &mut cache,
+        );
+        assert!(matches!(
+            result.err().unwrap().current_context(),
+            ConnectorError::InvalidInput(_)
+        ));
+        assert_eq!(
+            "currency,amount\nUSD,10\n",
+            fs::read_to_string(&input).unwrap()
+        );
+    }
+}
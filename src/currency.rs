@@ -1,7 +1,17 @@
 use std::cmp::Ordering;
 use std::fmt;
 use std::fmt::Formatter;
+use std::str::FromStr;
 
+/// Currency code with optional name.
+///
+/// ```
+/// use exchangeapp::Currency;
+///
+/// let currency = Currency::new("PLN", Some("Zloty".to_string()));
+/// assert_eq!("PLN", currency.get_short_code());
+/// assert_eq!("PLN (Zloty)", format!("{currency}"));
+/// ```
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Currency {
     short_code: String,
@@ -38,6 +48,53 @@ impl Currency {
     pub fn get_short_code(&self) -> &str {
         &self.short_code
     }
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// Base and target currency code written as `USD/PLN`.
+///
+/// ```
+/// use exchangeapp::CurrencyPair;
+///
+/// let pair: CurrencyPair = "usd/pln".parse().unwrap();
+/// assert_eq!("USD", pair.get_base());
+/// assert_eq!("PLN", pair.get_target());
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CurrencyPair {
+    base: String,
+    target: String,
+}
+
+impl CurrencyPair {
+    pub fn get_base(&self) -> &str {
+        &self.base
+    }
+    pub fn get_target(&self) -> &str {
+        &self.target
+    }
+}
+
+impl fmt::Display for CurrencyPair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.base, self.target)
+    }
+}
+
+impl FromStr for CurrencyPair {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('/') {
+            Some((base, target)) if !base.is_empty() && !target.is_empty() => Ok(CurrencyPair {
+                base: base.to_uppercase(),
+                target: target.to_uppercase(),
+            }),
+            _ => Err(format!("Currency pair {s} must look like USD/PLN")),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -54,4 +111,17 @@ mod tests {
         let currency = Currency::new("PLN", Some("Zloty".to_string()));
         assert_eq!("PLN (Zloty)", format!("{currency}"))
     }
+
+    #[test]
+    fn currency_pair() {
+        let pair = CurrencyPair::from_str("usd/PLN").unwrap();
+        assert_eq!("USD", pair.get_base());
+        assert_eq!("PLN", pair.get_target());
+        assert_eq!("USD/PLN", format!("{pair}"))
+    }
+
+    #[test]
+    fn currency_pair_without_separator() {
+        assert!(CurrencyPair::from_str("USDPLN").is_err())
+    }
 }
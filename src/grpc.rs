@@ -0,0 +1,331 @@
+use crate::connector::{error_message, ApiError, Connector, ConnectorError};
+use crate::currency::CurrencyPair;
+use crate::redact::redact;
+use crate::server::{bind, runtime, serve_with, ConnectorHandle};
+use crate::watch::current_rate;
+use bigdecimal::BigDecimal;
+use chrono::{NaiveDate, Utc};
+use error_stack::{Report, Result, ResultExt};
+use proto::exchange_service_server::{ExchangeService, ExchangeServiceServer};
+use proto::{
+    Currency, ExchangeReply, ExchangeRequest, HistoricalRequest, LatestRequest,
+    ListCurrenciesReply, ListCurrenciesRequest, Rate, RateUpdate, RatesReply, WatchRatesRequest,
+};
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{Code, Request, Response, Status};
+
+/// Messages and service generated from `proto/exchange.proto`
+#[allow(clippy::pedantic)]
+pub mod proto {
+    tonic::include_proto!("exchangeapp.v1");
+}
+
+const DEFAULT_WATCH_INTERVAL: u64 = 60;
+
+/// gRPC service calling the connector through its handle
+struct Service {
+    connector: ConnectorHandle,
+}
+
+impl Service {
+    async fn call<T: Send + 'static>(
+        &self,
+        call: impl FnOnce(&dyn Connector) -> Result<T, ConnectorError> + Send + 'static,
+    ) -> std::result::Result<T, Status> {
+        self.connector.call(call).await.map_err(|err| status(&err))
+    }
+}
+
+/// gRPC status of a connector error, details of the report are redacted
+fn status(err: &Report<ConnectorError>) -> Status {
+    let code = match (err.current_context(), err.downcast_ref::<ApiError>()) {
+        (ConnectorError::ApiError, Some(ApiError::InvalidCurrency))
+        | (ConnectorError::InvalidInput(_), _) => Code::InvalidArgument,
+        (ConnectorError::ApiError, Some(ApiError::AuthorizationError))
+        | (ConnectorError::ApiKeyRequirements, _) => Code::Unauthenticated,
+        (ConnectorError::ApiError, Some(ApiError::PlanRestriction)) => Code::PermissionDenied,
+        (ConnectorError::ApiError, Some(ApiError::TooManyRequests | ApiError::QuotaExceeded))
+        | (ConnectorError::BudgetExceeded | ConnectorError::RateLimited, _) => {
+            Code::ResourceExhausted
+        }
+        (ConnectorError::ApiError, Some(ApiError::ServerError))
+        | (ConnectorError::SendingError, _) => Code::Unavailable,
+        _ => Code::Internal,
+    };
+    Status::new(code, redact(&error_message(err)))
+}
+
+fn decimal(value: &str, name: &str) -> std::result::Result<BigDecimal, Status> {
+    BigDecimal::from_str(value)
+        .map_err(|_| Status::invalid_argument(format!("Invalid {name} {value}")))
+}
+
+fn targets(targets: &[String]) -> Option<Vec<String>> {
+    if targets.is_empty() {
+        None
+    } else {
+        Some(targets.iter().map(|target| target.to_uppercase()).collect())
+    }
+}
+
+fn rates_reply(rates: &[crate::LatestOutput]) -> RatesReply {
+    RatesReply {
+        rates: rates
+            .iter()
+            .map(|latest| Rate {
+                currency: latest.get_currency().get_short_code().to_string(),
+                rate: latest.get_rate().to_string(),
+            })
+            .collect(),
+    }
+}
+
+#[tonic::async_trait]
+impl ExchangeService for Service {
+    async fn exchange(
+        &self,
+        request: Request<ExchangeRequest>,
+    ) -> std::result::Result<Response<ExchangeReply>, Status> {
+        let request = request.into_inner();
+        let amount = decimal(&request.amount, "amount")?;
+        let output = self
+            .call(move |connector| {
+                connector.exchange(
+                    &request.source.to_uppercase(),
+                    &request.target.to_uppercase(),
+                    &amount,
+                )
+            })
+            .await?;
+        Ok(Response::new(ExchangeReply {
+            value: output.get_value().to_string(),
+            rate: output.get_rate().to_string(),
+        }))
+    }
+
+    async fn list_currencies(
+        &self,
+        _request: Request<ListCurrenciesRequest>,
+    ) -> std::result::Result<Response<ListCurrenciesReply>, Status> {
+        let currencies = self.call(|connector| connector.list_currencies()).await?;
+        Ok(Response::new(ListCurrenciesReply {
+            currencies: currencies
+                .iter()
+                .map(|currency| Currency {
+                    code: currency.get_short_code().to_string(),
+                    name: currency.get_name().map(str::to_string),
+                })
+                .collect(),
+        }))
+    }
+
+    async fn latest(
+        &self,
+        request: Request<LatestRequest>,
+    ) -> std::result::Result<Response<RatesReply>, Status> {
+        let request = request.into_inner();
+        let rates = self
+            .call(move |connector| {
+                connector.latest(&request.base.to_uppercase(), targets(&request.targets))
+            })
+            .await?;
+        Ok(Response::new(rates_reply(&rates)))
+    }
+
+    async fn historical(
+        &self,
+        request: Request<HistoricalRequest>,
+    ) -> std::result::Result<Response<RatesReply>, Status> {
+        let request = request.into_inner();
+        let date = NaiveDate::parse_from_str(&request.date, "%Y-%m-%d").map_err(|_| {
+            Status::invalid_argument(format!(
+                "Invalid date {}, expected YYYY-MM-DD",
+                request.date
+            ))
+        })?;
+        let rates = self
+            .call(move |connector| {
+                connector.historical(
+                    date,
+                    &request.base.to_uppercase(),
+                    targets(&request.targets),
+                )
+            })
+            .await?;
+        Ok(Response::new(rates_reply(&rates)))
+    }
+
+    type WatchRatesStream = ReceiverStream<std::result::Result<RateUpdate, Status>>;
+
+    async fn watch_rates(
+        &self,
+        request: Request<WatchRatesRequest>,
+    ) -> std::result::Result<Response<Self::WatchRatesStream>, Status> {
+        let request = request.into_inner();
+        let pairs = request
+            .pairs
+            .iter()
+            .map(|pair| CurrencyPair::from_str(pair).map_err(Status::invalid_argument))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if pairs.is_empty() {
+            return Err(Status::invalid_argument("No currency pairs to watch"));
+        }
+        let interval = match request.interval_seconds {
+            0 => DEFAULT_WATCH_INTERVAL,
+            seconds => u64::from(seconds),
+        };
+        let (sender, receiver) = tokio::sync::mpsc::channel(pairs.len());
+        let service = Service {
+            connector: self.connector.clone(),
+        };
+        tokio::spawn(async move {
+            let mut last_rates = vec![None; pairs.len()];
+            let mut ticks = tokio::time::interval(Duration::from_secs(interval));
+            loop {
+                ticks.tick().await;
+                for (pair, last_rate) in pairs.iter().zip(last_rates.iter_mut()) {
+                    let watched = pair.clone();
+                    let update = match service
+                        .call(move |connector| current_rate(connector, &watched))
+                        .await
+                    {
+                        Ok(rate) if last_rate.as_ref() == Some(&rate) => continue,
+                        Ok(rate) => {
+                            let update = RateUpdate {
+                                pair: pair.to_string(),
+                                rate: rate.to_string(),
+                                time: Utc::now().to_rfc3339(),
+                            };
+                            *last_rate = Some(rate);
+                            Ok(update)
+                        }
+                        Err(status) if status.code() == Code::ResourceExhausted => {
+                            tracing::warn!("Watching {pair}: {}", status.message());
+                            continue;
+                        }
+                        Err(status) => Err(status),
+                    };
+                    let failed = update.is_err();
+                    if sender.send(update).await.is_err() || failed {
+                        return;
+                    }
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
+
+/// Serve gRPC api on `addr` until the server fails, connector calls run on the calling thread
+pub fn serve(connector: &dyn Connector, addr: &str) -> Result<(), ConnectorError> {
+    serve_listener(connector, bind(addr)?)
+}
+
+/// Serve gRPC api on nonblocking `listener`, see [`serve`]
+pub fn serve_listener(
+    connector: &dyn Connector,
+    listener: TcpListener,
+) -> Result<(), ConnectorError> {
+    let addr = listener
+        .local_addr()
+        .change_context(ConnectorError::SendingError)?;
+    serve_with(connector, move |connector| {
+        runtime()?.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener)
+                .change_context(ConnectorError::SendingError)?;
+            tracing::info!("Serving gRPC on {addr}");
+            tonic::transport::Server::builder()
+                .add_service(ExchangeServiceServer::new(Service { connector }))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await
+                .change_context(ConnectorError::SendingError)
+                .attach_printable("Error with serving gRPC")
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::proto::exchange_service_client::ExchangeServiceClient;
+    use super::*;
+    use crate::connectors::mock_connector::MockConnector;
+    use std::thread;
+
+    fn start_server() -> String {
+        let listener = bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve_listener(&MockConnector::new(), listener));
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn exchange_with_decimal_strings() {
+        let mut client = ExchangeServiceClient::connect(start_server())
+            .await
+            .unwrap();
+        let reply = client
+            .exchange(ExchangeRequest {
+                source: "usd".to_string(),
+                target: "PLN".to_string(),
+                amount: "10.000000000000000000001".to_string(),
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!("40.000000000000000000004", reply.value);
+        assert_eq!("4", reply.rate);
+
+        let status = client
+            .exchange(ExchangeRequest {
+                source: "USD".to_string(),
+                target: "PLN".to_string(),
+                amount: "ten".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+
+        let reply = client
+            .latest(LatestRequest {
+                base: "usd".to_string(),
+                targets: vec!["pln".to_string()],
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(1, reply.rates.len());
+        assert_eq!("PLN", reply.rates[0].currency);
+        assert_eq!("4", reply.rates[0].rate);
+
+        let status = client
+            .latest(LatestRequest {
+                base: "XYZ".to_string(),
+                targets: Vec::new(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+        assert!(status.message().contains("XYZ"));
+    }
+
+    #[tokio::test]
+    async fn watch_rates_stream() {
+        let mut client = ExchangeServiceClient::connect(start_server())
+            .await
+            .unwrap();
+        let mut stream = client
+            .watch_rates(WatchRatesRequest {
+                pairs: vec!["USD/PLN".to_string()],
+                interval_seconds: 1,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let update = stream.message().await.unwrap().unwrap();
+        assert_eq!("USD/PLN", update.pair);
+        assert_eq!("4", update.rate);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::connector::{ConnectorError, LatestOutput};
+use crate::currency::Currency;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDateTime;
+use error_stack::{Result, ResultExt};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::str::FromStr;
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS rates (
+        recorded_at TEXT NOT NULL,
+        source TEXT NOT NULL,
+        base TEXT NOT NULL,
+        target TEXT NOT NULL,
+        rate TEXT NOT NULL,
+        PRIMARY KEY (recorded_at, source, base, target)
+    );
+    CREATE INDEX IF NOT EXISTS rates_base_recorded_at ON rates (base, recorded_at);
+";
+
+/// Exchange rates recorded from connectors, kept in a local `SQLite` database.
+///
+/// Timestamps are stored as UTC text and rates as decimal text, so no precision is lost.
+pub struct HistoryStore {
+    connection: Connection,
+}
+
+impl HistoryStore {
+    pub fn open(path: &Path) -> Result<Self, ConnectorError> {
+        let connection = Connection::open(path)
+            .change_context(ConnectorError::StorageError)
+            .attach_printable_lazy(|| format!("Error with opening {}", path.display()))?;
+        connection
+            .execute_batch(SCHEMA)
+            .change_context(ConnectorError::StorageError)?;
+        Ok(HistoryStore { connection })
+    }
+
+    pub fn record(
+        &mut self,
+        recorded_at: NaiveDateTime,
+        source: &str,
+        base: &str,
+        rates: &[LatestOutput],
+    ) -> Result<usize, ConnectorError> {
+        let recorded_at = recorded_at.format(TIMESTAMP_FORMAT).to_string();
+        let transaction = self
+            .connection
+            .transaction()
+            .change_context(ConnectorError::StorageError)?;
+        {
+            let mut statement = transaction
+                .prepare(
+                    "INSERT OR REPLACE INTO rates (recorded_at, source, base, target, rate)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )
+                .change_context(ConnectorError::StorageError)?;
+            for latest in rates {
+                statement
+                    .execute(params![
+                        recorded_at,
+                        source,
+                        base,
+                        latest.get_currency().get_short_code(),
+                        latest.get_rate().to_string()
+                    ])
+                    .change_context(ConnectorError::StorageError)?;
+            }
+        }
+        transaction
+            .commit()
+            .change_context(ConnectorError::StorageError)?;
+        Ok(rates.len())
+    }
+
+    /// Rates of the most recent snapshot for `base` recorded from `after` and before `before`.
+    ///
+    /// Bounds are compared as text, so a date like `2026-01-02` selects everything recorded until the end of 2026-01-01.
+    pub fn snapshot(
+        &self,
+        base: &str,
+        after: &str,
+        before: &str,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        let snapshot: Option<(String, String)> = self
+            .connection
+            .query_row(
+                "SELECT recorded_at, source FROM rates
+                 WHERE base = ?1 AND recorded_at >= ?2 AND recorded_at < ?3
+                 ORDER BY recorded_at DESC, source LIMIT 1",
+                params![base, after, before],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .change_context(ConnectorError::StorageError)?;
+        let Some((recorded_at, source)) = snapshot else {
+            return Ok(Vec::new());
+        };
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT target, rate FROM rates
+                 WHERE base = ?1 AND recorded_at = ?2 AND source = ?3
+                 ORDER BY target",
+            )
+            .change_context(ConnectorError::StorageError)?;
+        let rows = statement
+            .query_map(params![base, recorded_at, source], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .change_context(ConnectorError::StorageError)?;
+        let mut rates = Vec::new();
+        for row in rows {
+            let (target, rate) = row.change_context(ConnectorError::StorageError)?;
+            let rate = BigDecimal::from_str(&rate)
+                .change_context(ConnectorError::ParseError)
+                .attach_printable_lazy(|| format!("Invalid rate {rate} for {base}/{target}"))?;
+            rates.push(LatestOutput::new(Currency::new(&target, None), rate));
+        }
+        Ok(rates)
+    }
+
+    pub fn currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT base AS code FROM rates UNION SELECT target FROM rates ORDER BY code")
+            .change_context(ConnectorError::StorageError)?;
+        let rows = statement
+            .query_map([], |row| row.get::<_, String>(0))
+            .change_context(ConnectorError::StorageError)?;
+        let mut currencies = Vec::new();
+        for row in rows {
+            let code = row.change_context(ConnectorError::StorageError)?;
+            currencies.push(Currency::new(&code, None));
+        }
+        Ok(currencies)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 1, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    }
+
+    fn rates(pln: i32) -> Vec<LatestOutput> {
+        vec![LatestOutput::new(
+            Currency::new("PLN", None),
+            BigDecimal::from(pln),
+        )]
+    }
+
+    #[test]
+    fn most_recent_snapshot() {
+        let mut store = HistoryStore::open(Path::new(":memory:")).unwrap();
+        store.record(at(1, 12), "mock", "USD", &rates(4)).unwrap();
+        store.record(at(2, 12), "mock", "USD", &rates(5)).unwrap();
+        assert_eq!(rates(5), store.snapshot("USD", "", "9999-12-31").unwrap());
+        assert_eq!(rates(4), store.snapshot("USD", "", "2026-01-02").unwrap());
+        assert!(store.snapshot("USD", "", "2026-01-01").unwrap().is_empty());
+        assert!(store.snapshot("EUR", "", "9999-12-31").unwrap().is_empty());
+        assert!(store
+            .snapshot("USD", "2026-01-03", "9999-12-31")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn recorded_currencies() {
+        let mut store = HistoryStore::open(Path::new(":memory:")).unwrap();
+        store.record(at(1, 12), "mock", "USD", &rates(4)).unwrap();
+        assert_eq!(
+            vec![Currency::new("PLN", None), Currency::new("USD", None)],
+            store.currencies().unwrap()
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! Currency conversion with exchange rates fetched through pluggable connectors.
+//!
+//! ```
+//! use bigdecimal::BigDecimal;
+//! use exchangeapp::{Connector, ConnectorBuilder, ConnectorEnum};
+//!
+//! let connector = ConnectorBuilder::new(ConnectorEnum::Mock).build().unwrap();
+//! let output = connector
+//!     .exchange("USD", "PLN", &BigDecimal::from(10))
+//!     .unwrap();
+//! assert_eq!(&BigDecimal::from(40), output.get_value());
+//! ```
+#![feature(iterator_try_collect)]
+#![allow(clippy::missing_errors_doc, clippy::must_use_candidate)]
+// Helpers of optional connectors and commands are unused in builds without them
+#![cfg_attr(
+    not(all(
+        feature = "currencybeacon",
+        feature = "file",
+        feature = "generic-rest",
+        feature = "grpc",
+        feature = "local-history",
+        feature = "secrets",
+        feature = "webhook",
+        feature = "websocket"
+    )),
+    allow(dead_code)
+)]
+mod async_connector;
+mod audit;
+mod chart;
+#[doc(hidden)]
+pub mod cli;
+mod connector;
+mod connector_output;
+mod connectors;
+mod convert_file;
+mod currency;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "local-history")]
+mod history;
+mod metrics;
+mod portfolio;
+mod redact;
+mod rpc;
+#[cfg(feature = "secrets")]
+mod secrets;
+#[cfg(any(feature = "grpc", feature = "websocket"))]
+mod server;
+mod state_file;
+mod telemetry;
+mod usage;
+mod utility;
+mod watch;
+#[cfg(feature = "websocket")]
+mod ws;
+
+pub use async_connector::{AsyncConnector, BlockingConnector};
+pub use connector::{
+    create_connector, ApiError, Connector, ConnectorBuilder, ConnectorEnum, ConnectorError,
+};
+pub use connector_output::{ExchangeOutput, LatestOutput};
+#[cfg(feature = "currencybeacon")]
+pub use connectors::currencybeacon_connector::CurrencybeaconConnector;
+#[cfg(feature = "file")]
+pub use connectors::file_connector::FileConnector;
+#[cfg(feature = "generic-rest")]
+pub use connectors::generic_rest_connector::{GenericRestConnector, RestConfig};
+#[cfg(feature = "local-history")]
+pub use connectors::local_history_connector::LocalHistoryConnector;
+pub use connectors::mock_connector::{MockConnector, MockConnectorBuilder};
+pub use currency::{Currency, CurrencyPair};
@@ -0,0 +1,279 @@
+use crate::connector::{ApiError, ConnectorError};
+use crate::redact::redact;
+use bigdecimal::{BigDecimal, ToPrimitive};
+use error_stack::{Report, Result, ResultExt};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::LazyLock;
+use std::thread;
+use std::time::Duration;
+
+/// Metrics of the process, exposed in Prometheus text format by [`serve`]
+struct Metrics {
+    registry: Registry,
+    connector_requests: IntCounterVec,
+    connector_errors: IntCounterVec,
+    connector_latency: HistogramVec,
+    command_runs: IntCounterVec,
+    command_errors: IntCounterVec,
+    command_latency: HistogramVec,
+    cache_lookups: IntCounterVec,
+    quota_remaining: IntGaugeVec,
+    watch_rate: GaugeVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Time a client has to send its request before the connection is closed
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("exchangeapp".to_string()), None)
+            .expect("Prefix of metrics is valid");
+        let connector_requests = IntCounterVec::new(
+            Opts::new("connector_requests_total", "Calls of connector methods"),
+            &["connector", "method"],
+        );
+        let connector_errors = IntCounterVec::new(
+            Opts::new(
+                "connector_errors_total",
+                "Failed calls of connector methods",
+            ),
+            &["connector", "method", "error", "api_error"],
+        );
+        let connector_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "connector_latency_seconds",
+                "Latency of connector method calls",
+            ),
+            &["connector", "method"],
+        );
+        let command_runs = IntCounterVec::new(
+            Opts::new("command_runs_total", "Finished CLI commands"),
+            &["command"],
+        );
+        let command_errors = IntCounterVec::new(
+            Opts::new("command_errors_total", "Failed CLI commands"),
+            &["command", "error"],
+        );
+        let command_latency = HistogramVec::new(
+            HistogramOpts::new("command_latency_seconds", "Duration of CLI commands"),
+            &["command"],
+        );
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("cache_lookups_total", "Lookups of cached exchange rates"),
+            &["cache", "result"],
+        );
+        let quota_remaining = IntGaugeVec::new(
+            Opts::new(
+                "quota_remaining",
+                "Requests left in the budget of a connector",
+            ),
+            &["connector"],
+        );
+        let watch_rate = GaugeVec::new(
+            Opts::new("watch_rate", "Most recent exchange rate of a watched pair"),
+            &["pair"],
+        );
+        let metrics = Metrics {
+            registry,
+            connector_requests: connector_requests.expect("Connector requests metric is valid"),
+            connector_errors: connector_errors.expect("Connector errors metric is valid"),
+            connector_latency: connector_latency.expect("Connector latency metric is valid"),
+            command_runs: command_runs.expect("Command runs metric is valid"),
+            command_errors: command_errors.expect("Command errors metric is valid"),
+            command_latency: command_latency.expect("Command latency metric is valid"),
+            cache_lookups: cache_lookups.expect("Cache lookups metric is valid"),
+            quota_remaining: quota_remaining.expect("Quota metric is valid"),
+            watch_rate: watch_rate.expect("Watch rate metric is valid"),
+        };
+        let collectors: [Box<dyn prometheus::core::Collector>; 9] = [
+            Box::new(metrics.connector_requests.clone()),
+            Box::new(metrics.connector_errors.clone()),
+            Box::new(metrics.connector_latency.clone()),
+            Box::new(metrics.command_runs.clone()),
+            Box::new(metrics.command_errors.clone()),
+            Box::new(metrics.command_latency.clone()),
+            Box::new(metrics.cache_lookups.clone()),
+            Box::new(metrics.quota_remaining.clone()),
+            Box::new(metrics.watch_rate.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("Metrics are registered once");
+        }
+        metrics
+    }
+}
+
+/// Variant of the error and of the [`ApiError`] below it, `none` when there is no api error
+fn error_labels(err: &Report<ConnectorError>) -> (&'static str, &'static str) {
+    (
+        err.current_context().into(),
+        err.downcast_ref::<ApiError>().map_or("none", Into::into),
+    )
+}
+
+/// Count a call of connector `method` which took `latency`
+pub fn observe_connector_call<T>(
+    connector: &str,
+    method: &str,
+    latency: Duration,
+    result: &Result<T, ConnectorError>,
+) {
+    let labels = [connector, method];
+    METRICS.connector_requests.with_label_values(&labels).inc();
+    METRICS
+        .connector_latency
+        .with_label_values(&labels)
+        .observe(latency.as_secs_f64());
+    if let Err(err) = result {
+        let (error, api_error) = error_labels(err);
+        METRICS
+            .connector_errors
+            .with_label_values(&[connector, method, error, api_error])
+            .inc();
+    }
+}
+
+/// Count a finished CLI `command` which took `latency`
+pub fn observe_command(command: &str, latency: Duration) {
+    METRICS.command_runs.with_label_values(&[command]).inc();
+    METRICS
+        .command_latency
+        .with_label_values(&[command])
+        .observe(latency.as_secs_f64());
+}
+
+/// Count a failure of CLI `command`
+pub fn command_failed(command: &str, err: &Report<ConnectorError>) {
+    METRICS
+        .command_errors
+        .with_label_values(&[command, error_labels(err).0])
+        .inc();
+}
+
+/// Count a lookup in `cache`, the hit ratio is hits over all lookups
+pub fn observe_cache(cache: &str, hit: bool) {
+    let result = if hit { "hit" } else { "miss" };
+    METRICS
+        .cache_lookups
+        .with_label_values(&[cache, result])
+        .inc();
+}
+
+pub fn set_quota_remaining(connector: &str, remaining: u64) {
+    METRICS
+        .quota_remaining
+        .with_label_values(&[connector])
+        .set(i64::try_from(remaining).unwrap_or(i64::MAX));
+}
+
+pub fn set_watch_rate(pair: &str, rate: &BigDecimal) {
+    if let Some(rate) = rate.to_f64() {
+        METRICS.watch_rate.with_label_values(&[pair]).set(rate);
+    }
+}
+
+/// All metrics in Prometheus text format
+pub fn encode() -> Result<String, ConnectorError> {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&METRICS.registry.gather(), &mut buffer)
+        .change_context(ConnectorError::ParseError)
+        .attach_printable("Error with encoding metrics")?;
+    String::from_utf8(buffer)
+        .change_context(ConnectorError::ParseError)
+        .attach_printable("Error with encoding metrics")
+}
+
+/// Serve `GET /metrics` on `addr` from a background thread for the rest of the process
+pub fn serve(addr: &str) -> Result<SocketAddr, ConnectorError> {
+    let listener = TcpListener::bind(addr)
+        .change_context(ConnectorError::SendingError)
+        .attach_printable_lazy(|| format!("Error with listening on {addr}"))?;
+    let addr = listener
+        .local_addr()
+        .change_context(ConnectorError::SendingError)?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || {
+                if let Err(err) = respond(stream) {
+                    tracing::warn!("Error with serving metrics: {err}");
+                }
+            });
+        }
+    });
+    tracing::info!("Serving metrics on http://{addr}/metrics");
+    Ok(addr)
+}
+
+fn respond(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 2 {
+        line.clear();
+    }
+    let (status, content_type, body) = match request_line.split_whitespace().nth(1) {
+        Some("/metrics") => match encode() {
+            Ok(body) => ("200 OK", TextEncoder::new().format_type().to_string(), body),
+            Err(err) => (
+                "500 Internal Server Error",
+                "text/plain".to_string(),
+                redact(&format!("{err:?}")),
+            ),
+        },
+        _ => ("404 Not Found", "text/plain".to_string(), String::new()),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn metrics_endpoint() {
+        let err = Report::new(ApiError::QuotaExceeded).change_context(ConnectorError::ApiError);
+        observe_connector_call::<()>(
+            "test-connector",
+            "latest",
+            Duration::from_millis(20),
+            &Err(err),
+        );
+        observe_cache("test-cache", true);
+        set_watch_rate("USD/PLN", &BigDecimal::from(4));
+
+        let addr = serve("127.0.0.1:0").unwrap();
+        let _idle = TcpStream::connect(addr).unwrap();
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "GET /metrics HTTP/1.1\r\nHost: test\r\n\r\n").unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(
+            r#"exchangeapp_connector_errors_total{api_error="QuotaExceeded",connector="test-connector",error="ApiError",method="latest"} 1"#
+        ));
+        assert!(response.contains(
+            r#"exchangeapp_connector_latency_seconds_count{connector="test-connector",method="latest"} 1"#
+        ));
+        assert!(response
+            .contains(r#"exchangeapp_cache_lookups_total{cache="test-cache",result="hit"} 1"#));
+        assert!(response.contains(r#"exchangeapp_watch_rate{pair="USD/PLN"} 4"#));
+    }
+}
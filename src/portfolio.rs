@@ -0,0 +1,301 @@
+use crate::connector::{ConnectorError, LatestOutput};
+use crate::utility::round_trimmed;
+use bigdecimal::{BigDecimal, Zero};
+use error_stack::{Report, Result, ResultExt};
+use std::fmt;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq)]
+struct Holding {
+    currency: String,
+    amount: BigDecimal,
+}
+
+/// Holdings read from a toml or json file with a `holdings` table, e.g.
+///
+/// ```toml
+/// [holdings]
+/// USD = 1200
+/// EUR = "300.50"
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct Portfolio {
+    holdings: Vec<Holding>,
+}
+
+fn parse_amount(currency: &str, amount: &str) -> Result<Holding, ConnectorError> {
+    let amount = BigDecimal::from_str(amount)
+        .change_context(ConnectorError::ParseError)
+        .attach_printable_lazy(|| format!("Invalid amount {amount} of {currency}"))?;
+    Ok(Holding {
+        currency: currency.to_uppercase(),
+        amount,
+    })
+}
+
+fn no_holdings() -> Report<ConnectorError> {
+    let msg = "Portfolio file requires holdings table".to_string();
+    Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+}
+
+impl Portfolio {
+    pub fn load(path: &Path) -> Result<Self, ConnectorError> {
+        let text = fs::read_to_string(path)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Portfolio::from_json(&text),
+            _ => Portfolio::from_toml(&text),
+        }
+    }
+
+    fn from_toml(text: &str) -> Result<Self, ConnectorError> {
+        let table = text
+            .parse::<toml::Table>()
+            .change_context(ConnectorError::ParseError)?;
+        let holdings = table
+            .get("holdings")
+            .and_then(toml::Value::as_table)
+            .ok_or_else(no_holdings)?
+            .iter()
+            .map(|(currency, amount)| match amount {
+                toml::Value::String(amount) => parse_amount(currency, amount),
+                amount => parse_amount(currency, &amount.to_string()),
+            })
+            .try_collect::<Vec<_>>()?;
+        Ok(Portfolio { holdings })
+    }
+
+    fn from_json(text: &str) -> Result<Self, ConnectorError> {
+        let json = json::parse(text).change_context(ConnectorError::JsonParsingError)?;
+        if !json["holdings"].is_object() {
+            return Err(no_holdings());
+        }
+        let holdings = json["holdings"]
+            .entries()
+            .map(|(currency, amount)| parse_amount(currency, &amount.to_string()))
+            .try_collect::<Vec<_>>()?;
+        Ok(Portfolio { holdings })
+    }
+
+    /// Value every holding in `currency` using `rates` from `currency` to the holding currencies
+    pub fn value(
+        &self,
+        currency: &str,
+        label: &str,
+        rates: &[LatestOutput],
+    ) -> Result<Valuation, ConnectorError> {
+        let mut positions = Vec::new();
+        for holding in &self.holdings {
+            let value = if holding.currency == currency {
+                holding.amount.clone()
+            } else {
+                let rate = rates
+                    .iter()
+                    .find(|latest| latest.get_currency().get_short_code() == holding.currency)
+                    .map(LatestOutput::get_rate)
+                    .filter(|rate| !rate.is_zero())
+                    .ok_or_else(|| {
+                        let msg = format!("No exchange rate for {currency}/{}", holding.currency);
+                        Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+                    })?;
+                &holding.amount / rate
+            };
+            positions.push(Position {
+                currency: holding.currency.clone(),
+                amount: holding.amount.clone(),
+                value,
+            });
+        }
+        let total = positions
+            .iter()
+            .fold(BigDecimal::zero(), |total, position| {
+                total + &position.value
+            });
+        Ok(Valuation {
+            currency: currency.to_string(),
+            label: label.to_string(),
+            positions,
+            total,
+        })
+    }
+}
+
+fn percent(part: &BigDecimal, whole: &BigDecimal) -> BigDecimal {
+    if whole.is_zero() {
+        BigDecimal::zero()
+    } else {
+        (part * BigDecimal::from(100) / whole).round(2)
+    }
+}
+
+fn signed_percent(part: &BigDecimal, whole: &BigDecimal) -> String {
+    let percent = percent(part, whole);
+    if percent > BigDecimal::zero() {
+        format!("+{percent}%")
+    } else {
+        format!("{percent}%")
+    }
+}
+
+pub struct Position {
+    currency: String,
+    amount: BigDecimal,
+    value: BigDecimal,
+}
+
+pub struct Valuation {
+    currency: String,
+    label: String,
+    positions: Vec<Position>,
+    total: BigDecimal,
+}
+
+impl Valuation {
+    pub fn share(&self, position: &Position) -> BigDecimal {
+        percent(&position.value, &self.total)
+    }
+}
+
+impl fmt::Display for Valuation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Portfolio value in {} ({}):", self.currency, self.label)?;
+        for position in &self.positions {
+            writeln!(
+                f,
+                "{} {}: {} {} ({}%)",
+                position.currency,
+                position.amount,
+                round_trimmed(&position.value, 2),
+                self.currency,
+                self.share(position)
+            )?;
+        }
+        write!(
+            f,
+            "Total: {} {}",
+            round_trimmed(&self.total, 2),
+            self.currency
+        )
+    }
+}
+
+/// Change of portfolio value between two valuations of the same portfolio
+pub struct Comparison {
+    before: Valuation,
+    after: Valuation,
+}
+
+impl Comparison {
+    pub fn new(before: Valuation, after: Valuation) -> Self {
+        Comparison { before, after }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Portfolio value in {} ({} -> {}):",
+            self.after.currency, self.before.label, self.after.label
+        )?;
+        for (before, after) in self.before.positions.iter().zip(&self.after.positions) {
+            writeln!(
+                f,
+                "{} {}: {} -> {} {} ({})",
+                after.currency,
+                after.amount,
+                round_trimmed(&before.value, 2),
+                round_trimmed(&after.value, 2),
+                self.after.currency,
+                signed_percent(&(&after.value - &before.value), &before.value)
+            )?;
+        }
+        write!(
+            f,
+            "Total: {} -> {} {} ({})",
+            round_trimmed(&self.before.total, 2),
+            round_trimmed(&self.after.total, 2),
+            self.after.currency,
+            signed_percent(
+                &(&self.after.total - &self.before.total),
+                &self.before.total
+            )
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::currency::Currency;
+
+    fn rates(usd: &str) -> Vec<LatestOutput> {
+        [("GBP", "0.2"), ("USD", usd)]
+            .iter()
+            .map(|(code, rate)| {
+                LatestOutput::new(
+                    Currency::new(code, None),
+                    BigDecimal::from_str(rate).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    fn portfolio() -> Portfolio {
+        Portfolio::from_toml("[holdings]\nUSD = 1200\nGBP = \"50\"\nPLN = 100\n").unwrap()
+    }
+
+    #[test]
+    fn toml_and_json_holdings() {
+        assert_eq!(
+            portfolio(),
+            Portfolio::from_json(r#"{"holdings": {"USD": 1200, "GBP": "50", "PLN": 100}}"#)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_holdings() {
+        assert!(Portfolio::from_toml("USD = 1200").is_err());
+        assert!(Portfolio::from_json(r#"{"USD": 1200}"#).is_err());
+    }
+
+    #[test]
+    fn value() {
+        let valuation = portfolio().value("PLN", "latest", &rates("0.25")).unwrap();
+        assert_eq!(BigDecimal::from(5150), valuation.total);
+        assert_eq!(
+            BigDecimal::from_str("93.20").unwrap(),
+            valuation.share(&valuation.positions[0])
+        );
+        assert_eq!(
+            "Portfolio value in PLN (latest):\n\
+             USD 1200: 4800 PLN (93.20%)\n\
+             GBP 50: 250 PLN (4.85%)\n\
+             PLN 100: 100 PLN (1.94%)\n\
+             Total: 5150 PLN",
+            format!("{valuation}")
+        );
+    }
+
+    #[test]
+    fn missing_rate() {
+        let rates = rates("0.25")[..1].to_vec();
+        assert!(portfolio().value("PLN", "latest", &rates).is_err());
+    }
+
+    #[test]
+    fn compare() {
+        let portfolio = portfolio();
+        let before = portfolio
+            .value("PLN", "2026-01-01", &rates("0.25"))
+            .unwrap();
+        let after = portfolio.value("PLN", "2026-06-30", &rates("0.2")).unwrap();
+        assert!(format!("{}", Comparison::new(before, after))
+            .ends_with("Total: 5150 -> 6350 PLN (+23.30%)"));
+    }
+}
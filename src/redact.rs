@@ -0,0 +1,77 @@
+use std::sync::RwLock;
+
+pub const REDACTED: &str = "REDACTED";
+
+/// Query parameters whose values are always redacted, even if the secret was never registered
+const SECRET_PARAMS: [&str; 4] = ["api_key=", "apikey=", "access_key=", "token="];
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Register a value, e.g. api key, which is replaced with `REDACTED` by [`redact`]
+pub fn register(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+    let mut secrets = SECRETS
+        .write()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    if !secrets.iter().any(|registered| registered == secret) {
+        secrets.push(secret.to_string());
+    }
+}
+
+/// `text` with registered secrets and values of secret query parameters replaced with `REDACTED`,
+/// used for everything connectors log or attach to error reports
+pub fn redact(text: &str) -> String {
+    let secrets = SECRETS
+        .read()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    let text = secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret, REDACTED)
+    });
+    SECRET_PARAMS
+        .iter()
+        .fold(text, |text, param| redact_param(&text, param))
+}
+
+fn redact_param(text: &str, param: &str) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(param) {
+        let (before, after) = rest.split_at(start + param.len());
+        redacted.push_str(before);
+        let end = after
+            .find(|c: char| c == '&' || c == '"' || c == '\'' || c.is_whitespace())
+            .unwrap_or(after.len());
+        if end > 0 {
+            redacted.push_str(REDACTED);
+        }
+        rest = &after[end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registered_secrets() {
+        register("qwerty12345");
+        assert_eq!("key REDACTED in text", redact("key qwerty12345 in text"));
+    }
+
+    #[test]
+    fn secret_params() {
+        assert_eq!(
+            "https://api.test/latest?api_key=REDACTED&base=USD",
+            redact("https://api.test/latest?api_key=UNKNOWN&base=USD")
+        );
+        assert_eq!(
+            "url \"https://api.test/?access_key=REDACTED\" and token=REDACTED",
+            redact("url \"https://api.test/?access_key=A1\" and token=B2")
+        );
+        assert_eq!("api_key=&base=USD", redact("api_key=&base=USD"));
+    }
+}
@@ -0,0 +1,585 @@
+use crate::connector::{error_message, ApiError, Connector, ConnectorError};
+use crate::connector_output::LatestOutput;
+use crate::redact::redact;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+const PARSE_ERROR: i32 = -32700;
+const INVALID_REQUEST: i32 = -32600;
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Model Context Protocol versions answered to `initialize`, newest first
+const PROTOCOL_VERSIONS: [&str; 3] = ["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Code of the JSON-RPC error for `err`, one per variant in the range reserved for servers
+pub fn error_code(err: &ConnectorError) -> i32 {
+    match err {
+        ConnectorError::InvalidInput(_) => -32001,
+        ConnectorError::ApiKeyRequirements => -32002,
+        ConnectorError::ApiError => -32003,
+        ConnectorError::SendingError => -32004,
+        ConnectorError::JsonParsingError => -32005,
+        ConnectorError::ParseError => -32006,
+        ConnectorError::StorageError => -32007,
+        ConnectorError::FileError => -32008,
+        ConnectorError::BudgetExceeded => -32009,
+        ConnectorError::RateLimited => -32010,
+    }
+}
+
+/// Error object of a response
+#[derive(Debug)]
+struct RpcError {
+    code: i32,
+    message: String,
+    data: JsonValue,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+            data: JsonValue::Null,
+        }
+    }
+    fn to_json(&self) -> JsonValue {
+        let mut json = json::object! { code: self.code, message: self.message.clone() };
+        if !self.data.is_null() {
+            json["data"] = self.data.clone();
+        }
+        json
+    }
+}
+
+impl From<Report<ConnectorError>> for RpcError {
+    fn from(err: Report<ConnectorError>) -> Self {
+        let api_error: Option<&str> = err.downcast_ref::<ApiError>().map(Into::into);
+        RpcError {
+            code: error_code(err.current_context()),
+            message: redact(&error_message(&err)),
+            data: json::object! {
+                error: <&str>::from(err.current_context()),
+                api_error: api_error,
+            },
+        }
+    }
+}
+
+/// Method of the interface, also listed as a tool
+struct Method {
+    name: &'static str,
+    description: &'static str,
+    /// JSON Schema of the params object
+    schema: fn() -> JsonValue,
+    call: fn(&dyn Connector, &JsonValue) -> std::result::Result<JsonValue, RpcError>,
+}
+
+const METHODS: [Method; 4] = [
+    Method {
+        name: "exchange",
+        description: "Exchange amount of source currency to target currency",
+        schema: exchange_schema,
+        call: exchange,
+    },
+    Method {
+        name: "latest",
+        description: "Latest exchange rates of base currency",
+        schema: latest_schema,
+        call: latest,
+    },
+    Method {
+        name: "list_currencies",
+        description: "All available currencies",
+        schema: list_currencies_schema,
+        call: list_currencies,
+    },
+    Method {
+        name: "historical",
+        description: "Exchange rates of base currency on a date",
+        schema: historical_schema,
+        call: historical,
+    },
+];
+
+fn currency_schema() -> JsonValue {
+    json::object! { type: "string", description: "Currency code, e.g. USD" }
+}
+
+fn targets_schema() -> JsonValue {
+    json::object! {
+        type: "array",
+        items: currency_schema(),
+        description: "Target currency codes, all currencies when left out",
+    }
+}
+
+fn exchange_schema() -> JsonValue {
+    json::object! {
+        type: "object",
+        properties: {
+            source: currency_schema(),
+            target: currency_schema(),
+            amount: {
+                type: ["string", "number"],
+                description: "Amount of source currency, a decimal string keeps its precision",
+            },
+        },
+        required: ["source", "target", "amount"],
+        additionalProperties: false,
+    }
+}
+
+fn latest_schema() -> JsonValue {
+    json::object! {
+        type: "object",
+        properties: { base: currency_schema(), targets: targets_schema() },
+        required: ["base"],
+        additionalProperties: false,
+    }
+}
+
+fn list_currencies_schema() -> JsonValue {
+    json::object! { type: "object", properties: {}, additionalProperties: false }
+}
+
+fn historical_schema() -> JsonValue {
+    json::object! {
+        type: "object",
+        properties: {
+            date: { type: "string", format: "date", description: "Date in YYYY-MM-DD format" },
+            base: currency_schema(),
+            targets: targets_schema(),
+        },
+        required: ["date", "base"],
+        additionalProperties: false,
+    }
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Short(_) | JsonValue::String(_) => "string",
+        JsonValue::Number(_) => "number",
+        JsonValue::Boolean(_) => "boolean",
+        JsonValue::Object(_) => "object",
+        JsonValue::Array(_) => "array",
+    }
+}
+
+/// Error of `value` at `path` against the keywords of JSON Schema used by the methods: `type`,
+/// `properties`, `required`, `additionalProperties` and `items`
+fn validate(schema: &JsonValue, value: &JsonValue, path: &str) -> Option<String> {
+    let types = &schema["type"];
+    if !types.is_null() && !types.contains(type_name(value)) && types != type_name(value) {
+        let expected = match types {
+            JsonValue::Array(types) => types.iter().map(ToString::to_string).collect(),
+            types => vec![types.to_string()],
+        };
+        return Some(format!("{path} must be {}", expected.join(" or ")));
+    }
+    if let JsonValue::Object(object) = value {
+        if let Some(key) = schema["required"]
+            .members()
+            .filter_map(JsonValue::as_str)
+            .find(|key| object.get(key).is_none_or(JsonValue::is_null))
+        {
+            return Some(format!("{path}.{key} is required"));
+        }
+        for (key, value) in object.iter() {
+            let property = &schema["properties"][key];
+            if property.is_null() {
+                if schema["additionalProperties"] == false {
+                    return Some(format!("{path}.{key} is not expected"));
+                }
+            } else if let Some(err) = validate(property, value, &format!("{path}.{key}")) {
+                return Some(err);
+            }
+        }
+    }
+    if let JsonValue::Array(items) = value {
+        for (i, item) in items.iter().enumerate() {
+            if let Some(err) = validate(&schema["items"], item, &format!("{path}[{i}]")) {
+                return Some(err);
+            }
+        }
+    }
+    None
+}
+
+fn currency(params: &JsonValue, key: &str) -> String {
+    params[key].as_str().unwrap_or_default().to_uppercase()
+}
+
+fn targets(params: &JsonValue) -> Option<Vec<String>> {
+    let targets = params["targets"]
+        .members()
+        .filter_map(JsonValue::as_str)
+        .map(str::to_uppercase)
+        .collect::<Vec<_>>();
+    (!targets.is_empty()).then_some(targets)
+}
+
+fn rates(rates: &[LatestOutput]) -> JsonValue {
+    json::object! {
+        rates: rates
+            .iter()
+            .map(|latest| json::object! {
+                currency: latest.get_currency().get_short_code(),
+                rate: latest.get_rate().to_string(),
+            })
+            .collect::<Vec<_>>(),
+    }
+}
+
+fn exchange(
+    connector: &dyn Connector,
+    params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    let amount = &params["amount"];
+    let amount = amount
+        .as_str()
+        .map_or_else(|| amount.dump(), str::to_string);
+    let amount = BigDecimal::from_str(&amount)
+        .map_err(|_| RpcError::new(INVALID_PARAMS, format!("Invalid amount {amount}")))?;
+    let output = connector.exchange(
+        &currency(params, "source"),
+        &currency(params, "target"),
+        &amount,
+    )?;
+    Ok(json::object! {
+        value: output.get_value().to_string(),
+        rate: output.get_rate().to_string(),
+    })
+}
+
+fn latest(
+    connector: &dyn Connector,
+    params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    Ok(rates(
+        &connector.latest(&currency(params, "base"), targets(params))?,
+    ))
+}
+
+fn list_currencies(
+    connector: &dyn Connector,
+    _params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    let currencies = connector.list_currencies()?;
+    Ok(json::object! {
+        currencies: currencies
+            .iter()
+            .map(|currency| json::object! {
+                code: currency.get_short_code(),
+                name: currency.get_name(),
+            })
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn historical(
+    connector: &dyn Connector,
+    params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    let date = params["date"].as_str().unwrap_or_default();
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+        RpcError::new(
+            INVALID_PARAMS,
+            format!("Invalid date {date}, expected YYYY-MM-DD"),
+        )
+    })?;
+    Ok(rates(&connector.historical(
+        date,
+        &currency(params, "base"),
+        targets(params),
+    )?))
+}
+
+/// Call `method` with `params` checked against its schema, left out params are an empty object
+fn invoke(
+    method: &Method,
+    connector: &dyn Connector,
+    params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    let empty = JsonValue::new_object();
+    let params = if params.is_null() { &empty } else { params };
+    if let Some(err) = validate(&(method.schema)(), params, "params") {
+        return Err(RpcError::new(INVALID_PARAMS, err));
+    }
+    (method.call)(connector, params)
+}
+
+fn initialize(params: &JsonValue) -> JsonValue {
+    let requested = params["protocolVersion"].as_str().unwrap_or_default();
+    let version = PROTOCOL_VERSIONS
+        .into_iter()
+        .find(|version| *version == requested)
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    json::object! {
+        protocolVersion: version,
+        capabilities: { tools: {} },
+        serverInfo: { name: env!("CARGO_PKG_NAME"), version: env!("CARGO_PKG_VERSION") },
+    }
+}
+
+fn list_tools() -> JsonValue {
+    json::object! {
+        tools: METHODS
+            .iter()
+            .map(|method| json::object! {
+                name: method.name,
+                description: method.description,
+                inputSchema: (method.schema)(),
+            })
+            .collect::<Vec<_>>(),
+    }
+}
+
+/// Result of a tool call, errors of the method are returned to the caller as a failed result
+fn call_tool(
+    connector: &dyn Connector,
+    params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    let name = params["name"].as_str().unwrap_or_default();
+    let method = METHODS
+        .iter()
+        .find(|method| method.name == name)
+        .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("Unknown tool {name}")))?;
+    let (content, is_error) = match invoke(method, connector, &params["arguments"]) {
+        Ok(result) => (result, false),
+        Err(err) => (json::object! { error: err.to_json() }, true),
+    };
+    Ok(json::object! {
+        content: [{ type: "text", text: content.dump() }],
+        structuredContent: content,
+        isError: is_error,
+    })
+}
+
+fn call(
+    connector: &dyn Connector,
+    method: &str,
+    params: &JsonValue,
+) -> std::result::Result<JsonValue, RpcError> {
+    match method {
+        "initialize" => Ok(initialize(params)),
+        "ping" => Ok(JsonValue::new_object()),
+        "tools/list" => Ok(list_tools()),
+        "tools/call" => call_tool(connector, params),
+        name => {
+            let method = METHODS
+                .iter()
+                .find(|method| method.name == name)
+                .ok_or_else(|| {
+                    RpcError::new(METHOD_NOT_FOUND, format!("Method {name} not found"))
+                })?;
+            invoke(method, connector, params)
+        }
+    }
+}
+
+fn response(id: JsonValue, result: std::result::Result<JsonValue, RpcError>) -> JsonValue {
+    let mut response = json::object! { jsonrpc: "2.0", id: id };
+    match result {
+        Ok(result) => response["result"] = result,
+        Err(err) => response["error"] = err.to_json(),
+    }
+    response
+}
+
+/// Response to `request`, none to a notification
+fn handle(connector: &dyn Connector, request: &JsonValue) -> Option<JsonValue> {
+    let id = request["id"].clone();
+    if !request.is_object() || request["jsonrpc"] != "2.0" || !request["method"].is_string() {
+        let err = RpcError::new(INVALID_REQUEST, "Invalid request");
+        return Some(response(id, Err(err)));
+    }
+    let method = request["method"].as_str().unwrap_or_default();
+    let span = tracing::info_span!("rpc", method);
+    let result = span.in_scope(|| call(connector, method, &request["params"]));
+    if let Err(err) = &result {
+        tracing::info!("Error {}: {}", err.code, err.message);
+    }
+    request.has_key("id").then(|| response(id, result))
+}
+
+/// Answer JSON-RPC 2.0 requests read line by line from `input` until it ends, every response
+/// is written to `output` as one line.
+///
+/// Methods `exchange`, `latest`, `list_currencies` and `historical` take a params object
+/// described by a JSON Schema. The schemas are listed by `tools/list`, and the methods are
+/// called as Model Context Protocol tools with `tools/call` too.
+pub fn serve(
+    connector: &dyn Connector,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> Result<(), ConnectorError> {
+    for line in input.lines() {
+        let line = line
+            .change_context(ConnectorError::FileError)
+            .attach_printable("Error with reading request")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match json::parse(&line) {
+            Err(_) => Some(response(
+                JsonValue::Null,
+                Err(RpcError::new(PARSE_ERROR, "Parse error")),
+            )),
+            Ok(JsonValue::Array(requests)) if requests.is_empty() => Some(response(
+                JsonValue::Null,
+                Err(RpcError::new(INVALID_REQUEST, "Invalid request")),
+            )),
+            Ok(JsonValue::Array(requests)) => {
+                let responses = requests
+                    .iter()
+                    .filter_map(|request| handle(connector, request))
+                    .collect::<Vec<_>>();
+                (!responses.is_empty()).then_some(JsonValue::Array(responses))
+            }
+            Ok(request) => handle(connector, &request),
+        };
+        if let Some(response) = response {
+            writeln!(output, "{}", response.dump())
+                .and_then(|()| output.flush())
+                .change_context(ConnectorError::FileError)
+                .attach_printable("Error with writing response")?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::mock_connector::MockConnector;
+
+    fn responses(input: &str) -> Vec<JsonValue> {
+        let mut output = Vec::new();
+        serve(&MockConnector::new(), input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| json::parse(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn methods() {
+        let responses = responses(concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "exchange", "params": {"source": "usd", "target": "PLN", "amount": "10.000000000000000000001"}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": "b", "method": "latest", "params": {"base": "USD", "targets": ["PLN"]}}"#,
+            "\n\n",
+            r#"{"jsonrpc": "2.0", "method": "latest", "params": {"base": "USD"}}"#,
+            "\n",
+            r#"[{"jsonrpc": "2.0", "id": 3, "method": "list_currencies"}, {"jsonrpc": "2.0", "id": 4, "method": "exchange", "params": {"source": "GBP", "target": "USD", "amount": 8}}]"#,
+        ));
+        assert_eq!(3, responses.len());
+        assert_eq!(1, responses[0]["id"]);
+        assert_eq!("40.000000000000000000004", responses[0]["result"]["value"]);
+        assert_eq!("b", responses[1]["id"]);
+        assert_eq!(
+            json::array![{ currency: "PLN", rate: "4" }],
+            responses[1]["result"]["rates"]
+        );
+        assert_eq!(3, responses[2][0]["result"]["currencies"].len());
+        let value = responses[2][1]["result"]["value"].as_str().unwrap();
+        assert_eq!(BigDecimal::from(10), BigDecimal::from_str(value).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let responses = responses(concat!(
+            "{\"jsonrpc\": \"2.0\", \"id\": 1, \"method\": \n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "convert"}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "exchange", "params": {"source": "USD", "amount": "1"}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "latest", "params": {"base": "USD", "targets": [1]}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 5, "method": "historical", "params": {"date": "yesterday", "base": "USD"}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 6, "method": "latest", "params": {"base": "XYZ"}}"#,
+            "\n",
+            r#"{"id": 7, "method": "latest"}"#,
+            "\n",
+            r#"[]"#,
+        ));
+        let codes = responses
+            .iter()
+            .map(|response| response["error"]["code"].as_i32().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                PARSE_ERROR,
+                METHOD_NOT_FOUND,
+                INVALID_PARAMS,
+                INVALID_PARAMS,
+                INVALID_PARAMS,
+                -32001,
+                INVALID_REQUEST,
+                INVALID_REQUEST
+            ],
+            codes
+        );
+        assert_eq!(
+            "params.target is required",
+            responses[2]["error"]["message"]
+        );
+        assert_eq!(
+            "params.targets[0] must be string",
+            responses[3]["error"]["message"]
+        );
+        assert_eq!("InvalidInput", responses[5]["error"]["data"]["error"]);
+        assert!(responses[5]["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("XYZ"));
+        assert_eq!(7, responses[6]["id"]);
+    }
+
+    #[test]
+    fn error_codes_of_api_errors() {
+        let err = RpcError::from(
+            Report::new(ApiError::QuotaExceeded).change_context(ConnectorError::ApiError),
+        );
+        assert_eq!(-32003, err.code);
+        assert_eq!("QuotaExceeded", err.data["api_error"]);
+        assert_eq!("Api request quota is used up", err.message);
+    }
+
+    #[test]
+    fn tools() {
+        let responses = responses(concat!(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {"protocolVersion": "2024-11-05", "capabilities": {}}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "method": "notifications/initialized"}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "tools/list"}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "exchange", "arguments": {"source": "USD", "target": "PLN", "amount": "2"}}}"#,
+            "\n",
+            r#"{"jsonrpc": "2.0", "id": 4, "method": "tools/call", "params": {"name": "latest", "arguments": {"base": "XYZ"}}}"#,
+        ));
+        assert_eq!(4, responses.len());
+        assert_eq!("2024-11-05", responses[0]["result"]["protocolVersion"]);
+        let tools = &responses[1]["result"]["tools"];
+        assert_eq!(4, tools.len());
+        assert_eq!("exchange", tools[0]["name"]);
+        assert_eq!(exchange_schema(), tools[0]["inputSchema"]);
+        assert_eq!(false, responses[2]["result"]["isError"]);
+        assert_eq!("8", responses[2]["result"]["structuredContent"]["value"]);
+        assert_eq!(true, responses[3]["result"]["isError"]);
+        assert_eq!(
+            -32001,
+            responses[3]["result"]["structuredContent"]["error"]["code"]
+        );
+    }
+}
@@ -0,0 +1,218 @@
+use crate::connector::ConnectorError;
+use crate::state_file;
+use crate::utility::{get_secrets_key_file, get_secrets_passphrase, get_secrets_path};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+fn invalid(msg: &str) -> Report<ConnectorError> {
+    Report::new(ConnectorError::InvalidInput(msg.to_string())).attach_printable(msg.to_string())
+}
+
+/// Passphrase or key file protecting a [`SecretsStore`]
+pub enum SecretsKey {
+    Passphrase(String),
+    /// Whole content of the file is used as the passphrase
+    KeyFile(PathBuf),
+}
+
+impl SecretsKey {
+    /// Key file from `EAPP_SECRETS_KEY_FILE`, passphrase from `EAPP_SECRETS_PASSPHRASE`,
+    /// otherwise passphrase is prompted for
+    pub fn from_env() -> Result<Self, ConnectorError> {
+        if let Some(path) = get_secrets_key_file() {
+            return Ok(SecretsKey::KeyFile(path));
+        }
+        if let Some(passphrase) = get_secrets_passphrase() {
+            return Ok(SecretsKey::Passphrase(passphrase));
+        }
+        rpassword::prompt_password("Secrets passphrase: ")
+            .map(SecretsKey::Passphrase)
+            .change_context(ConnectorError::InvalidInput(
+                "No secrets passphrase".to_string(),
+            ))
+            .attach_printable("Set EAPP_SECRETS_PASSPHRASE or EAPP_SECRETS_KEY_FILE")
+    }
+    fn derive(&self, salt: &[u8]) -> Result<Key, ConnectorError> {
+        let material =
+            match self {
+                SecretsKey::Passphrase(passphrase) => passphrase.as_bytes().to_vec(),
+                SecretsKey::KeyFile(path) => fs::read(path)
+                    .change_context(ConnectorError::FileError)
+                    .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?,
+            };
+        if material.is_empty() {
+            return Err(invalid("Secrets passphrase is empty"));
+        }
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(&material, salt, &mut key)
+            .map_err(|err| invalid(&format!("Error with deriving secrets key: {err}")))?;
+        Ok(key)
+    }
+}
+
+/// Named secrets, e.g. api keys of connectors, in a file encrypted with ChaCha20-Poly1305
+/// under a key derived from [`SecretsKey`] with Argon2id.
+///
+/// File is a json object with `version`, hex encoded `salt`, `nonce` and `ciphertext`.
+pub struct SecretsStore {
+    path: PathBuf,
+    key: SecretsKey,
+    secrets: BTreeMap<String, String>,
+}
+
+impl SecretsStore {
+    /// Store at `EAPP_SECRETS_FILE` unlocked with [`SecretsKey::from_env`]
+    pub fn new() -> Result<Self, ConnectorError> {
+        SecretsStore::open(&get_secrets_path(), SecretsKey::from_env()?)
+    }
+    /// Decrypt store at `path`, which is empty when the file doesn't exist
+    pub fn open(path: &Path, key: SecretsKey) -> Result<Self, ConnectorError> {
+        let secrets = if path.exists() {
+            decrypt(path, &key)?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(SecretsStore {
+            path: path.to_path_buf(),
+            key,
+            secrets,
+        })
+    }
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.secrets.get(name).map(String::as_str)
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.secrets.keys().map(String::as_str)
+    }
+    pub fn set(&mut self, name: &str, secret: &str) {
+        self.secrets.insert(name.to_string(), secret.to_string());
+    }
+    /// Remove secret `name`, returns whether it was stored
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.secrets.remove(name).is_some()
+    }
+    /// Encrypt the store with fresh salt and nonce
+    pub fn save(&self) -> Result<(), ConnectorError> {
+        let salt = rand::random::<[u8; SALT_LEN]>();
+        let cipher = ChaCha20Poly1305::new(&self.key.derive(&salt)?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let mut plaintext = JsonValue::new_object();
+        for (name, secret) in &self.secrets {
+            plaintext[name.as_str()] = secret.as_str().into();
+        }
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext.dump().as_bytes())
+            .map_err(|_| invalid("Error with encrypting secrets"))?;
+        let json = json::object! {
+            version: VERSION,
+            salt: hex::encode(salt),
+            nonce: hex::encode(nonce),
+            ciphertext: hex::encode(ciphertext),
+        };
+        state_file::write_atomic_private(&self.path, &json.pretty(2))
+    }
+}
+
+fn decrypt(path: &Path, key: &SecretsKey) -> Result<BTreeMap<String, String>, ConnectorError> {
+    let text = fs::read_to_string(path)
+        .change_context(ConnectorError::FileError)
+        .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+    let json = json::parse(&text)
+        .change_context(ConnectorError::JsonParsingError)
+        .attach_printable_lazy(|| format!("Error in secrets file {}", path.display()))?;
+    if json["version"].as_u32() != Some(VERSION) {
+        return Err(invalid(&format!(
+            "Unsupported version of secrets file {}",
+            path.display()
+        )));
+    }
+    let field = |name: &str| {
+        json[name]
+            .as_str()
+            .and_then(|value| hex::decode(value).ok())
+            .ok_or_else(|| invalid(&format!("Invalid {name} in {}", path.display())))
+    };
+    let (salt, nonce, ciphertext) = (field("salt")?, field("nonce")?, field("ciphertext")?);
+    if nonce.len() != NONCE_LEN {
+        return Err(invalid(&format!("Invalid nonce in {}", path.display())));
+    }
+    let plaintext = ChaCha20Poly1305::new(&key.derive(&salt)?)
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| invalid("Cannot decrypt secrets, wrong passphrase or key file"))?;
+    let secrets = String::from_utf8(plaintext)
+        .ok()
+        .and_then(|plaintext| json::parse(&plaintext).ok())
+        .ok_or_else(|| invalid(&format!("Invalid secrets in {}", path.display())))?;
+    Ok(secrets
+        .entries()
+        .filter_map(|(name, secret)| Some((name.to_string(), secret.as_str()?.to_string())))
+        .collect())
+}
+
+/// Secret `name` from the store at `EAPP_SECRETS_FILE`, `None` when there is no store
+pub fn lookup(name: &str) -> Result<Option<String>, ConnectorError> {
+    if !get_secrets_path().exists() {
+        return Ok(None);
+    }
+    Ok(SecretsStore::new()?.get(name).map(str::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passphrase(passphrase: &str) -> SecretsKey {
+        SecretsKey::Passphrase(passphrase.to_string())
+    }
+
+    #[test]
+    fn encrypted_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let mut store = SecretsStore::open(&path, passphrase("correct horse")).unwrap();
+        store.set("currencybeacon", "SECRET_KEY");
+        store.set("generic-rest", "OTHER_KEY");
+        store.save().unwrap();
+        assert!(!fs::read_to_string(&path).unwrap().contains("SECRET_KEY"));
+
+        let mut store = SecretsStore::open(&path, passphrase("correct horse")).unwrap();
+        assert_eq!(Some("SECRET_KEY"), store.get("currencybeacon"));
+        assert!(store.remove("generic-rest"));
+        assert!(!store.remove("generic-rest"));
+        store.save().unwrap();
+        let store = SecretsStore::open(&path, passphrase("correct horse")).unwrap();
+        assert_eq!(vec!["currencybeacon"], store.names().collect::<Vec<_>>());
+
+        let err = SecretsStore::open(&path, passphrase("wrong"))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::InvalidInput(_)
+        ));
+    }
+
+    #[test]
+    fn key_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("secrets.json");
+        let key_path = dir.path().join("key");
+        fs::write(&key_path, "random key material").unwrap();
+        let mut store = SecretsStore::open(&path, SecretsKey::KeyFile(key_path.clone())).unwrap();
+        store.set("currencybeacon", "SECRET_KEY");
+        store.save().unwrap();
+        let store = SecretsStore::open(&path, SecretsKey::KeyFile(key_path.clone())).unwrap();
+        assert_eq!(Some("SECRET_KEY"), store.get("currencybeacon"));
+    }
+}
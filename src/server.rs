@@ -0,0 +1,71 @@
+use crate::connector::{Connector, ConnectorError};
+use error_stack::{Report, Result, ResultExt};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use tokio::runtime::Runtime;
+use tokio::sync::oneshot;
+
+/// Call of the connector, run on the thread which owns it
+type Job = Box<dyn FnOnce(&dyn Connector) + Send>;
+
+/// Handle of servers to the connector, which is not `Send` and stays on the thread
+/// running [`serve_with`]
+#[derive(Clone)]
+pub struct ConnectorHandle {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl ConnectorHandle {
+    /// Run `call` with the connector and wait for its result
+    pub async fn call<T: Send + 'static>(
+        &self,
+        call: impl FnOnce(&dyn Connector) -> Result<T, ConnectorError> + Send + 'static,
+    ) -> Result<T, ConnectorError> {
+        let shut_down =
+            || Report::new(ConnectorError::SendingError).attach_printable("Connector is shut down");
+        let (sender, receiver) = oneshot::channel();
+        self.jobs
+            .send(Box::new(move |connector: &dyn Connector| {
+                let _ = sender.send(call(connector));
+            }))
+            .map_err(|_| shut_down())?;
+        receiver.await.map_err(|_| shut_down())?
+    }
+}
+
+/// Run `server` on its own thread and the calls it makes to `connector` on the calling thread,
+/// until the server returns
+pub fn serve_with(
+    connector: &dyn Connector,
+    server: impl FnOnce(ConnectorHandle) -> Result<(), ConnectorError> + Send + 'static,
+) -> Result<(), ConnectorError> {
+    let (jobs, receiver) = mpsc::channel::<Job>();
+    let server = thread::spawn(move || server(ConnectorHandle { jobs }));
+    for job in receiver {
+        job(connector);
+    }
+    server.join().unwrap_or_else(|_| {
+        Err(Report::new(ConnectorError::SendingError).attach_printable("Server panicked"))
+    })
+}
+
+/// Listener on `addr` ready to be handed to tokio
+pub fn bind(addr: &str) -> Result<TcpListener, ConnectorError> {
+    let listener = TcpListener::bind(addr)
+        .change_context(ConnectorError::SendingError)
+        .attach_printable_lazy(|| format!("Error with listening on {addr}"))?;
+    listener
+        .set_nonblocking(true)
+        .change_context(ConnectorError::SendingError)?;
+    Ok(listener)
+}
+
+/// Multi-threaded runtime of a server
+pub fn runtime() -> Result<Runtime, ConnectorError> {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .change_context(ConnectorError::SendingError)
+        .attach_printable("Error with starting async runtime")
+}
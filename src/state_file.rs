@@ -0,0 +1,162 @@
+use crate::connector::ConnectorError;
+use error_stack::{Report, Result, ResultExt};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCK_RETRY: Duration = Duration::from_millis(5);
+
+/// Exclusive advisory lock on a lock file next to a state file shared by processes.
+/// It is released on drop, or by the OS when the process dies.
+pub struct FileLock(File);
+
+impl FileLock {
+    /// Lock at `path`, `None` while another process or handle holds it
+    fn try_acquire(path: &Path) -> Result<Option<Self>, ConnectorError> {
+        create_parent(path)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with opening {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(FileLock(file))),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(err)) => Err(Report::new(err)
+                .change_context(ConnectorError::FileError)
+                .attach_printable(format!("Error with locking {}", path.display()))),
+        }
+    }
+    fn timeout(path: &Path) -> Report<ConnectorError> {
+        Report::new(ConnectorError::FileError)
+            .attach_printable(format!("Timeout waiting for lock file {}", path.display()))
+    }
+    /// Lock at `path`, the file is opened on a blocking thread and the task sleeps
+    /// while waiting for it
+    pub async fn acquire_async(path: &Path) -> Result<Self, ConnectorError> {
+        let start = Instant::now();
+        loop {
+            let lock_path = path.to_path_buf();
+            let lock = tokio::task::spawn_blocking(move || FileLock::try_acquire(&lock_path))
+                .await
+                .change_context(ConnectorError::FileError)??;
+            if let Some(lock) = lock {
+                return Ok(lock);
+            }
+            if start.elapsed() > LOCK_TIMEOUT {
+                return Err(FileLock::timeout(path));
+            }
+            tokio::time::sleep(LOCK_RETRY).await;
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+/// Lock file of state file at `path`
+pub fn lock_path(path: &Path) -> PathBuf {
+    path.with_extension("lock")
+}
+
+/// Run `update` of state file at `path` on a blocking thread while holding its lock
+pub async fn update<T: Send + 'static>(
+    path: &Path,
+    update: impl FnOnce() -> Result<T, ConnectorError> + Send + 'static,
+) -> Result<T, ConnectorError> {
+    let lock = FileLock::acquire_async(&lock_path(path)).await?;
+    tokio::task::spawn_blocking(move || {
+        let _lock = lock;
+        update()
+    })
+    .await
+    .change_context(ConnectorError::FileError)?
+}
+
+fn create_parent(path: &Path) -> Result<(), ConnectorError> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with creating {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// Write `text` to a temporary file renamed to `path`, so readers never see a torn file
+pub fn write_atomic(path: &Path, text: &str) -> Result<(), ConnectorError> {
+    replace(
+        path,
+        text,
+        OpenOptions::new().write(true).create(true).truncate(true),
+    )
+}
+
+/// Like [`write_atomic`], but the file is created readable and writable only by its owner
+pub fn write_atomic_private(path: &Path, text: &str) -> Result<(), ConnectorError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    replace(path, text, &options)
+}
+
+fn replace(path: &Path, text: &str, options: &OpenOptions) -> Result<(), ConnectorError> {
+    create_parent(path)?;
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    match fs::remove_file(&temporary) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+    .and_then(|()| options.open(&temporary))
+    .and_then(|mut file| file.write_all(text.as_bytes()))
+    .and_then(|()| fs::rename(&temporary, path))
+    .change_context(ConnectorError::FileError)
+    .attach_printable_lazy(|| format!("Error with writing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lock.lock");
+        let lock = FileLock::acquire_async(&path).await.unwrap();
+        assert!(FileLock::try_acquire(&path).unwrap().is_none());
+        drop(lock);
+        assert!(FileLock::acquire_async(&path).await.is_ok());
+    }
+
+    #[test]
+    fn atomic_write_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("atomic.json");
+        write_atomic(&path, "{}").unwrap();
+        write_atomic(&path, r#"{"a": 1}"#).unwrap();
+        assert_eq!(r#"{"a": 1}"#, fs::read_to_string(&path).unwrap());
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_write_is_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("private.json");
+        write_atomic_private(&path, "{}").unwrap();
+        write_atomic_private(&path, r#"{"a": 1}"#).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(r#"{"a": 1}"#, fs::read_to_string(&path).unwrap());
+        assert_eq!(0o600, mode & 0o777);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::async_connector::AsyncConnector;
+use crate::connector::{Connector, ConnectorError, ExchangeOutput, LatestOutput};
+use crate::currency::Currency;
+use crate::metrics;
+use crate::redact::redact;
+use async_trait::async_trait;
+use bigdecimal::BigDecimal;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use error_stack::Result;
+use std::future::Future;
+use std::io::{self, IsTerminal, Write};
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+/// Format of log lines written to standard error
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable lines
+    #[default]
+    Text,
+    /// One json object per line, e.g. for a log aggregator
+    Json,
+}
+
+/// Standard error with api keys redacted from everything written to it
+struct RedactingWriter(io::Stderr);
+
+impl Write for RedactingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .write_all(redact(&String::from_utf8_lossy(buf)).as_bytes())?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Filter of `RUST_LOG`, otherwise warnings and `verbosity` levels more of this crate:
+/// info with 1, debug with 2 and trace with 3
+fn filter(verbosity: u8) -> EnvFilter {
+    if let Ok(filter) = EnvFilter::try_from_default_env() {
+        return filter;
+    }
+    let level = match verbosity {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    EnvFilter::new(format!("warn,exchangeapp={level}"))
+}
+
+/// Install subscriber writing events and closed spans, with their fields and duration,
+/// to standard error. Records of the `log` crate are forwarded to it.
+pub fn init(format: LogFormat, verbosity: u8) {
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter(verbosity))
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(io::stderr().is_terminal())
+        .with_writer(|| RedactingWriter(io::stderr()));
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().with_current_span(true).init(),
+    }
+}
+
+/// Connector recording a `connector` span and metrics with connector name, method, latency
+/// and outcome around every call
+pub struct TracedConnector<C> {
+    name: String,
+    connector: C,
+}
+
+impl<C> TracedConnector<C> {
+    pub fn new(name: &str, connector: C) -> Self {
+        TracedConnector {
+            name: name.to_string(),
+            connector,
+        }
+    }
+    fn span(&self, method: &'static str) -> Span {
+        tracing::info_span!(
+            "connector",
+            connector = self.name,
+            method,
+            latency_ms = Empty,
+            error = Empty
+        )
+    }
+    /// Record latency since `start` and error of `result` in `span` and in metrics
+    fn finish<T>(
+        &self,
+        method: &'static str,
+        span: &Span,
+        start: Instant,
+        result: &Result<T, ConnectorError>,
+    ) {
+        let latency = start.elapsed();
+        span.record("latency_ms", latency.as_secs_f64() * 1000.0);
+        if let Err(err) = result {
+            span.record("error", tracing::field::debug(err.current_context()));
+        }
+        metrics::observe_connector_call(&self.name, method, latency, result);
+    }
+    fn call<T>(
+        &self,
+        method: &'static str,
+        call: impl FnOnce() -> Result<T, ConnectorError>,
+    ) -> Result<T, ConnectorError> {
+        let span = self.span(method);
+        let start = Instant::now();
+        let result = span.in_scope(call);
+        self.finish(method, &span, start, &result);
+        result
+    }
+    async fn call_async<T>(
+        &self,
+        method: &'static str,
+        call: impl Future<Output = Result<T, ConnectorError>>,
+    ) -> Result<T, ConnectorError> {
+        let span = self.span(method);
+        let start = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        self.finish(method, &span, start, &result);
+        result
+    }
+}
+
+impl<C: Connector> Connector for TracedConnector<C> {
+    fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        self.call("exchange", || {
+            self.connector.exchange(source, target, amount)
+        })
+    }
+    fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        self.call("list_currencies", || self.connector.list_currencies())
+    }
+    fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.call("latest", || self.connector.latest(base, target))
+    }
+    fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.call("historical", || {
+            self.connector.historical(date, base, target)
+        })
+    }
+}
+
+#[async_trait]
+impl<C: AsyncConnector> AsyncConnector for TracedConnector<C> {
+    async fn exchange(
+        &self,
+        source: &str,
+        target: &str,
+        amount: &BigDecimal,
+    ) -> Result<ExchangeOutput, ConnectorError> {
+        self.call_async("exchange", self.connector.exchange(source, target, amount))
+            .await
+    }
+    async fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+        self.call_async("list_currencies", self.connector.list_currencies())
+            .await
+    }
+    async fn latest(
+        &self,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.call_async("latest", self.connector.latest(base, target))
+            .await
+    }
+    async fn historical(
+        &self,
+        date: NaiveDate,
+        base: &str,
+        target: Option<Vec<String>>,
+    ) -> Result<Vec<LatestOutput>, ConnectorError> {
+        self.call_async("historical", self.connector.historical(date, base, target))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::mock_connector::MockConnector;
+
+    #[test]
+    fn traced_connector_passes_results_through() {
+        let connector = TracedConnector::new("mock", MockConnector::new());
+        assert_eq!(
+            ExchangeOutput::new(&BigDecimal::from(40), &BigDecimal::from(4)),
+            Connector::exchange(&connector, "USD", "PLN", &BigDecimal::from(10)).unwrap()
+        );
+        assert!(Connector::latest(&connector, "XYZ", None).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use crate::connector::{ConnectorEnum, ConnectorError};
+use crate::metrics;
+use crate::state_file::{self, write_atomic};
+use crate::utility::{connector_name, get_budget, get_budget_warning, get_usage_path};
+use chrono::{Datelike, Months, NaiveDate};
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use strum::IntoEnumIterator;
+
+/// Period after which a [`Budget`] resets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Month,
+}
+
+/// Maximum number of requests per period, e.g. `5000/month` or `300/day`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Budget {
+    limit: u64,
+    period: Period,
+}
+
+impl Budget {
+    pub fn new(limit: u64, period: Period) -> Self {
+        Budget { limit, period }
+    }
+}
+
+impl FromStr for Budget {
+    type Err = String;
+
+    /// Period defaults to month when omitted
+    fn from_str(budget: &str) -> std::result::Result<Self, Self::Err> {
+        let (limit, period) = budget.split_once('/').unwrap_or((budget, "month"));
+        let period = match period.trim() {
+            "day" => Period::Day,
+            "month" => Period::Month,
+            _ => {
+                return Err(format!(
+                    "Invalid budget period in {budget}, expected day or month"
+                ))
+            }
+        };
+        match limit.trim().parse::<u64>() {
+            Ok(limit) if limit > 0 => Ok(Budget { limit, period }),
+            _ => Err(format!("Invalid budget {budget}, expected e.g. 5000/month")),
+        }
+    }
+}
+
+impl fmt::Display for Budget {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.period {
+            Period::Day => write!(fmt, "{}/day", self.limit),
+            Period::Month => write!(fmt, "{}/month", self.limit),
+        }
+    }
+}
+
+/// Requests of every connector per day, stored as `{"connector": {"2024-05-01": 12}}`
+#[derive(Default)]
+struct UsageFile {
+    days: BTreeMap<String, BTreeMap<NaiveDate, u64>>,
+}
+
+impl UsageFile {
+    fn load(path: &Path) -> Result<Self, ConnectorError> {
+        if !path.exists() {
+            return Ok(UsageFile::default());
+        }
+        let text = fs::read_to_string(path)
+            .change_context(ConnectorError::FileError)
+            .attach_printable_lazy(|| format!("Error with reading {}", path.display()))?;
+        let json = json::parse(&text)
+            .change_context(ConnectorError::JsonParsingError)
+            .attach_printable_lazy(|| format!("Error in usage file {}", path.display()))?;
+        let mut usage = UsageFile::default();
+        for (connector, counts) in json.entries() {
+            let connector_days = usage.days.entry(connector.to_string()).or_default();
+            for (date, count) in counts.entries() {
+                let (Ok(date), Some(count)) = (NaiveDate::from_str(date), count.as_u64()) else {
+                    return Err(
+                        Report::new(ConnectorError::ParseError).attach_printable(format!(
+                            "Invalid usage of {connector} on {date} in {}",
+                            path.display()
+                        )),
+                    );
+                };
+                connector_days.insert(date, count);
+            }
+        }
+        Ok(usage)
+    }
+    fn save(&self, path: &Path) -> Result<(), ConnectorError> {
+        let mut json = JsonValue::new_object();
+        for (connector, counts) in &self.days {
+            let mut days = JsonValue::new_object();
+            for (date, count) in counts {
+                days[date.to_string().as_str()] = (*count).into();
+            }
+            json[connector.as_str()] = days;
+        }
+        write_atomic(path, &json.pretty(2))
+    }
+    fn count(&self, connector: &str, today: NaiveDate, period: Period) -> u64 {
+        self.days.get(connector).map_or(0, |counts| {
+            counts
+                .range(period_start(today, period)..=today)
+                .map(|(_, count)| count)
+                .sum()
+        })
+    }
+    /// Add a request and forget days before the previous month
+    fn add(&mut self, connector: &str, today: NaiveDate) {
+        let counts = self.days.entry(connector.to_string()).or_default();
+        *counts.entry(today).or_default() += 1;
+        let oldest = period_start(today, Period::Month)
+            .checked_sub_months(Months::new(1))
+            .unwrap_or(NaiveDate::MIN);
+        counts.retain(|date, _| *date >= oldest);
+    }
+}
+
+fn period_start(today: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => today,
+        Period::Month => today.with_day(1).unwrap_or(today),
+    }
+}
+
+fn period_reset(today: NaiveDate, period: Period) -> NaiveDate {
+    match period {
+        Period::Day => today.succ_opt(),
+        Period::Month => period_start(today, period).checked_add_months(Months::new(1)),
+    }
+    .unwrap_or(NaiveDate::MAX)
+}
+
+/// Counts requests of a connector in the usage file and refuses them once the budget is used up.
+///
+/// Budget is read from `EAPP_BUDGET_<CONNECTOR>`, e.g. `EAPP_BUDGET_CURRENCYBEACON=5000/month`,
+/// and a warning is logged after `EAPP_BUDGET_WARNING` percent of it.
+/// The usage file is shared by all processes, guarded by an advisory lock on a file next to it.
+#[derive(Clone)]
+pub struct UsageTracker {
+    connector: String,
+    path: PathBuf,
+    budget: Option<Budget>,
+    warning: u64,
+}
+
+impl UsageTracker {
+    pub fn new(connector: ConnectorEnum) -> Result<Self, ConnectorError> {
+        let budget = budget_of(connector)?;
+        Ok(UsageTracker::with_budget(
+            &connector_name(connector),
+            &get_usage_path(),
+            budget,
+            get_budget_warning(),
+        ))
+    }
+    /// Tracker of `connector` in usage file at `path`, warning after `warning` percent of `budget`
+    pub fn with_budget(connector: &str, path: &Path, budget: Option<Budget>, warning: u64) -> Self {
+        UsageTracker {
+            connector: connector.to_string(),
+            path: path.to_path_buf(),
+            budget,
+            warning,
+        }
+    }
+    /// Count a request made on `today`, fails with [`ConnectorError::BudgetExceeded`]
+    /// without counting when the budget is used up
+    pub async fn count_request(&self, today: NaiveDate) -> Result<(), ConnectorError> {
+        let tracker = self.clone();
+        state_file::update(&self.path, move || tracker.count(today)).await
+    }
+    /// Count a request in the usage file, the caller holds its lock
+    fn count(&self, today: NaiveDate) -> Result<(), ConnectorError> {
+        let mut usage = UsageFile::load(&self.path)?;
+        if let Some(budget) = self.budget {
+            let used = usage.count(&self.connector, today, budget.period);
+            if used >= budget.limit {
+                return Err(
+                    Report::new(ConnectorError::BudgetExceeded).attach_printable(format!(
+                        "Budget of {budget} requests for {} is used up, it resets on {}",
+                        self.connector,
+                        period_reset(today, budget.period)
+                    )),
+                );
+            }
+            metrics::set_quota_remaining(&self.connector, budget.limit - used - 1);
+            if u128::from(used + 1) * 100 >= u128::from(budget.limit) * u128::from(self.warning) {
+                tracing::warn!("{} used {} of {budget} requests", self.connector, used + 1);
+            }
+        }
+        usage.add(&self.connector, today);
+        usage.save(&self.path)
+    }
+}
+
+fn budget_of(connector: ConnectorEnum) -> Result<Option<Budget>, ConnectorError> {
+    get_budget(connector)
+        .map(|budget| {
+            Budget::from_str(&budget)
+                .map_err(|err| Report::new(ConnectorError::InvalidInput(err)))
+                .attach_printable_lazy(|| {
+                    format!("Invalid budget of {}", connector_name(connector))
+                })
+        })
+        .transpose()
+}
+
+/// Requests made by a connector today and this month
+#[derive(Debug, PartialEq, Eq)]
+pub struct Usage {
+    connector: String,
+    today: u64,
+    month: u64,
+    budget: Option<Budget>,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "{}: {} today, {} this month",
+            self.connector, self.today, self.month
+        )?;
+        if let Some(budget) = self.budget {
+            let used = match budget.period {
+                Period::Day => self.today,
+                Period::Month => self.month,
+            };
+            #[allow(clippy::cast_precision_loss)]
+            let percent = used as f64 * 100.0 / budget.limit as f64;
+            write!(fmt, ", budget {budget} ({percent:.1}% used)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Usage of connectors which made requests or have a budget
+pub fn usage_report(path: &Path, today: NaiveDate) -> Result<Vec<Usage>, ConnectorError> {
+    let usage = UsageFile::load(path)?;
+    ConnectorEnum::iter()
+        .map(|connector| {
+            let name = connector_name(connector);
+            Ok(Usage {
+                today: usage.count(&name, today, Period::Day),
+                month: usage.count(&name, today, Period::Month),
+                budget: budget_of(connector)?,
+                connector: name,
+            })
+        })
+        .filter(|usage| !matches!(usage, Ok(usage) if usage.month == 0 && usage.budget.is_none()))
+        .try_collect::<Vec<_>>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn date(date: &str) -> NaiveDate {
+        NaiveDate::from_str(date).unwrap()
+    }
+
+    #[test]
+    fn budgets() {
+        assert_eq!(
+            Ok(Budget::new(5000, Period::Month)),
+            Budget::from_str("5000")
+        );
+        assert_eq!(
+            Ok(Budget::new(300, Period::Day)),
+            Budget::from_str("300/day")
+        );
+        assert!(Budget::from_str("0/month").is_err());
+        assert!(Budget::from_str("300/week").is_err());
+    }
+
+    #[tokio::test]
+    async fn refused_after_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        let tracker =
+            UsageTracker::with_budget("mock", &path, Some(Budget::new(2, Period::Day)), 80);
+        tracker.count_request(date("2024-05-01")).await.unwrap();
+        tracker.count_request(date("2024-05-01")).await.unwrap();
+        let err = tracker.count_request(date("2024-05-01")).await.unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConnectorError::BudgetExceeded
+        ));
+        assert!(format!("{err:?}").contains("2024-05-02"));
+        tracker.count_request(date("2024-05-02")).await.unwrap();
+        let usage = UsageFile::load(&path).unwrap();
+        assert_eq!(3, usage.count("mock", date("2024-05-02"), Period::Month));
+        assert_eq!(1, usage.count("mock", date("2024-05-02"), Period::Day));
+    }
+
+    #[tokio::test]
+    async fn month_resets_and_old_days_forgotten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        let tracker =
+            UsageTracker::with_budget("mock", &path, Some(Budget::new(1, Period::Month)), 80);
+        tracker.count_request(date("2024-03-31")).await.unwrap();
+        tracker.count_request(date("2024-04-30")).await.unwrap();
+        assert!(tracker.count_request(date("2024-04-30")).await.is_err());
+        tracker.count_request(date("2024-05-01")).await.unwrap();
+        let usage = UsageFile::load(&path).unwrap();
+        assert_eq!(
+            vec![date("2024-04-30"), date("2024-05-01")],
+            usage.days["mock"].keys().copied().collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn trackers_share_usage_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("usage.json");
+        let budget = Some(Budget::new(10, Period::Day));
+        let trackers = (0..4)
+            .map(|_| UsageTracker::with_budget("mock", &path, budget, 80))
+            .collect::<Vec<_>>();
+        let results = futures::future::join_all(
+            trackers
+                .iter()
+                .cycle()
+                .take(12)
+                .map(|tracker| tracker.count_request(date("2024-05-01"))),
+        )
+        .await;
+        let usage = UsageFile::load(&path).unwrap();
+        assert_eq!(10, results.iter().filter(|result| result.is_ok()).count());
+        assert_eq!(10, usage.count("mock", date("2024-05-01"), Period::Day));
+    }
+}
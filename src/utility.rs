@@ -1,10 +1,44 @@
-use crate::connector::ConnectorEnum;
+use crate::connector::{ConnectorEnum, ConnectorError};
+use bigdecimal::BigDecimal;
+use clap::ValueEnum;
 use std::env;
+use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::Duration;
 use strum::EnumProperty;
 use strum::IntoEnumIterator;
 
 const CONNECTOR_ENV: &str = "EAPP_CONNECTOR";
+const HISTORY_DB_ENV: &str = "EAPP_HISTORY_DB";
+const RECORD_BASES_ENV: &str = "EAPP_RECORD_BASES";
+const DEFAULT_HISTORY_DB: &str = "history.db";
+const PORTFOLIO_ENV: &str = "EAPP_PORTFOLIO";
+const DEFAULT_PORTFOLIO: &str = "portfolio.toml";
+const HTTP_MODE_ENV: &str = "EAPP_HTTP_MODE";
+const HTTP_CASSETTE_ENV: &str = "EAPP_HTTP_CASSETTE";
+const DEFAULT_HTTP_CASSETTE: &str = "fixtures/http.json";
+const MOCK_CONFIG_ENV: &str = "EAPP_MOCK_CONFIG";
+const RATES_FILE_ENV: &str = "EAPP_RATES_FILE";
+const DEFAULT_RATES_FILE: &str = "rates.csv";
+const REST_CONFIG_ENV: &str = "EAPP_REST_CONFIG";
+const DEFAULT_REST_CONFIG: &str = "rest_connector.toml";
+const USAGE_FILE_ENV: &str = "EAPP_USAGE_FILE";
+const DEFAULT_USAGE_FILE: &str = "usage.json";
+const BUDGET_ENV_PREFIX: &str = "EAPP_BUDGET_";
+const BUDGET_WARNING_ENV: &str = "EAPP_BUDGET_WARNING";
+const DEFAULT_BUDGET_WARNING: u64 = 80;
+const RATE_LIMIT_ENV_PREFIX: &str = "EAPP_RATE_LIMIT_";
+const RATE_LIMIT_MODE_ENV: &str = "EAPP_RATE_LIMIT_MODE";
+const RATE_LIMIT_FILE_ENV: &str = "EAPP_RATE_LIMIT_FILE";
+const DEFAULT_RATE_LIMIT_FILE: &str = "rate_limit.json";
+const KEY_ROTATION_ENV: &str = "EAPP_KEY_ROTATION";
+const API_KEYS_FILE_ENV: &str = "EAPP_API_KEYS_FILE";
+const DEFAULT_API_KEYS_FILE: &str = "api_keys.json";
+const SECRETS_FILE_ENV: &str = "EAPP_SECRETS_FILE";
+const DEFAULT_SECRETS_FILE: &str = "secrets.json";
+const SECRETS_KEY_FILE_ENV: &str = "EAPP_SECRETS_KEY_FILE";
+const SECRETS_PASSPHRASE_ENV: &str = "EAPP_SECRETS_PASSPHRASE";
+const METRICS_ADDR_ENV: &str = "EAPP_METRICS_ADDR";
 pub const API_KEY_ENV: &str = "API_KEY_ENV";
 
 pub fn show_connectors() {
@@ -16,8 +50,22 @@ pub fn show_connectors() {
         } else {
             print!("  ");
         }
-        println!("{}", format!("{connector}").to_lowercase());
+        println!("{}", connector_name(connector));
     }
+    if !ConnectorEnum::MISSING.is_empty() {
+        println!("Not included in this build:");
+        for name in ConnectorEnum::MISSING {
+            println!("  {name} (cargo feature {name})");
+        }
+    }
+}
+
+/// Name of the connector as accepted by `--set-connector`
+pub fn connector_name(connector: ConnectorEnum) -> String {
+    connector.to_possible_value().map_or_else(
+        || format!("{connector}"),
+        |value| value.get_name().to_string(),
+    )
 }
 
 pub fn set_connector(connector: ConnectorEnum) {
@@ -27,7 +75,7 @@ pub fn set_connector(connector: ConnectorEnum) {
 
 pub fn get_connector() -> ConnectorEnum {
     match env::var(CONNECTOR_ENV) {
-        Ok(connector_string) => match ConnectorEnum::from_str(&connector_string) {
+        Ok(connector_string) => match <ConnectorEnum as FromStr>::from_str(&connector_string) {
             Ok(connector) => connector,
             Err(_) => ConnectorEnum::Mock,
         },
@@ -36,10 +84,204 @@ pub fn get_connector() -> ConnectorEnum {
 }
 
 pub fn set_apikey(apikey: &str, connector: ConnectorEnum) {
-    if connector.get_str(API_KEY_ENV).unwrap() == "" {
-        eprintln!("this connector not require api key");
+    match connector
+        .get_str(API_KEY_ENV)
+        .filter(|name| !name.is_empty())
+    {
+        Some(name) => env::set_var(name, apikey),
+        None => eprintln!("this connector not require api key"),
+    }
+}
+
+/// Api key of the connector from its env variable, otherwise from the secrets store
+pub fn get_api_key(
+    connector: ConnectorEnum,
+) -> error_stack::Result<Option<String>, ConnectorError> {
+    let Some(name) = connector
+        .get_str(API_KEY_ENV)
+        .filter(|name| !name.is_empty())
+    else {
+        return Ok(None);
+    };
+    if let Some(api_key) = env::var(name).ok().filter(|api_key| !api_key.is_empty()) {
+        return Ok(Some(api_key));
+    }
+    #[cfg(feature = "secrets")]
+    return crate::secrets::lookup(&connector_name(connector));
+    #[cfg(not(feature = "secrets"))]
+    Ok(None)
+}
+
+pub fn get_secrets_path() -> PathBuf {
+    env::var(SECRETS_FILE_ENV)
+        .unwrap_or_else(|_| DEFAULT_SECRETS_FILE.to_string())
+        .into()
+}
+
+pub fn get_secrets_key_file() -> Option<PathBuf> {
+    env::var(SECRETS_KEY_FILE_ENV)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Address of the metrics endpoint, e.g. `127.0.0.1:9100`
+pub fn get_metrics_addr() -> Option<String> {
+    env::var(METRICS_ADDR_ENV)
+        .ok()
+        .filter(|addr| !addr.is_empty())
+}
+
+pub fn get_secrets_passphrase() -> Option<String> {
+    env::var(SECRETS_PASSPHRASE_ENV)
+        .ok()
+        .filter(|passphrase| !passphrase.is_empty())
+}
+
+pub fn get_history_db_path() -> PathBuf {
+    env::var(HISTORY_DB_ENV)
+        .unwrap_or_else(|_| DEFAULT_HISTORY_DB.to_string())
+        .into()
+}
+
+pub fn get_portfolio_path() -> PathBuf {
+    env::var(PORTFOLIO_ENV)
+        .unwrap_or_else(|_| DEFAULT_PORTFOLIO.to_string())
+        .into()
+}
+
+pub fn get_rest_config_path() -> PathBuf {
+    env::var(REST_CONFIG_ENV)
+        .unwrap_or_else(|_| DEFAULT_REST_CONFIG.to_string())
+        .into()
+}
+
+/// Http mode of the connectors: `live`, `record` or `replay`
+pub fn get_http_mode() -> String {
+    env::var(HTTP_MODE_ENV)
+        .ok()
+        .filter(|mode| !mode.is_empty())
+        .unwrap_or_else(|| "live".to_string())
+}
+
+pub fn get_http_cassette_path() -> PathBuf {
+    env::var(HTTP_CASSETTE_ENV)
+        .unwrap_or_else(|_| DEFAULT_HTTP_CASSETTE.to_string())
+        .into()
+}
+
+/// Mock connector config, the built-in mock is used when not set
+pub fn get_mock_config_path() -> Option<PathBuf> {
+    env::var(MOCK_CONFIG_ENV)
+        .ok()
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+}
+
+pub fn get_rates_file_path() -> PathBuf {
+    env::var(RATES_FILE_ENV)
+        .unwrap_or_else(|_| DEFAULT_RATES_FILE.to_string())
+        .into()
+}
+
+pub fn get_usage_path() -> PathBuf {
+    env::var(USAGE_FILE_ENV)
+        .unwrap_or_else(|_| DEFAULT_USAGE_FILE.to_string())
+        .into()
+}
+
+/// Value of env variable `prefix` followed by connector name, e.g. `EAPP_BUDGET_GENERIC_REST`
+fn get_connector_var(prefix: &str, connector: ConnectorEnum) -> Option<String> {
+    let name = connector_name(connector).to_uppercase().replace('-', "_");
+    env::var(format!("{prefix}{name}"))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+/// Request budget of the connector from `EAPP_BUDGET_<CONNECTOR>`, e.g. `5000/month`
+pub fn get_budget(connector: ConnectorEnum) -> Option<String> {
+    get_connector_var(BUDGET_ENV_PREFIX, connector)
+}
+
+/// Percent of the budget after which every request logs a warning
+pub fn get_budget_warning() -> u64 {
+    env::var(BUDGET_WARNING_ENV)
+        .ok()
+        .and_then(|warning| warning.parse().ok())
+        .unwrap_or(DEFAULT_BUDGET_WARNING)
+}
+
+/// Rate limit of the connector from `EAPP_RATE_LIMIT_<CONNECTOR>`, e.g. `10/1m`
+pub fn get_rate_limit(connector: ConnectorEnum) -> Option<String> {
+    get_connector_var(RATE_LIMIT_ENV_PREFIX, connector)
+}
+
+/// What to do when rate limit is reached: `wait` or `fail`
+pub fn get_rate_limit_mode() -> String {
+    env::var(RATE_LIMIT_MODE_ENV)
+        .ok()
+        .filter(|mode| !mode.is_empty())
+        .unwrap_or_else(|| "wait".to_string())
+}
+
+pub fn get_rate_limit_path() -> PathBuf {
+    env::var(RATE_LIMIT_FILE_ENV)
+        .unwrap_or_else(|_| DEFAULT_RATE_LIMIT_FILE.to_string())
+        .into()
+}
+
+/// Order of trying api keys: `failover` or `round-robin`
+pub fn get_key_rotation() -> String {
+    env::var(KEY_ROTATION_ENV)
+        .ok()
+        .filter(|rotation| !rotation.is_empty())
+        .unwrap_or_else(|| "failover".to_string())
+}
+
+pub fn get_api_keys_path() -> PathBuf {
+    env::var(API_KEYS_FILE_ENV)
+        .unwrap_or_else(|_| DEFAULT_API_KEYS_FILE.to_string())
+        .into()
+}
+
+pub fn get_record_bases() -> Vec<String> {
+    env::var(RECORD_BASES_ENV)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|base| !base.is_empty())
+        .map(str::to_uppercase)
+        .collect()
+}
+
+/// `value` rounded to `digits` decimal places without trailing zeros
+pub fn round_trimmed(value: &BigDecimal, digits: i64) -> BigDecimal {
+    let value = value.round(digits).normalized();
+    if value.fractional_digit_count() < 0 {
+        value.with_scale(0)
     } else {
-        env::set_var(connector.get_str(API_KEY_ENV).unwrap(), apikey);
+        value
+    }
+}
+
+/// Parse durations like `30s`, `5m` or `1h`; plain numbers are seconds
+pub fn parse_duration(duration: &str) -> Result<Duration, String> {
+    let (value, multiplier) = match duration.char_indices().last() {
+        Some((i, 's')) => (&duration[..i], 1),
+        Some((i, 'm')) => (&duration[..i], 60),
+        Some((i, 'h')) => (&duration[..i], 60 * 60),
+        Some((i, 'd')) => (&duration[..i], 24 * 60 * 60),
+        _ => (duration, 1),
+    };
+    match value
+        .parse::<u64>()
+        .map(|value| value.checked_mul(multiplier))
+    {
+        Ok(Some(seconds)) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+        Ok(None) => Err(format!("Duration {duration} is too long")),
+        _ => Err(format!(
+            "Invalid duration {duration}, expected e.g. 30s, 5m or 1h"
+        )),
     }
 }
 
@@ -53,4 +295,22 @@ mod tests {
             assert_eq!(connector, ConnectorEnum::Mock);
         }
     }
+
+    #[test]
+    fn durations() {
+        assert_eq!(Ok(Duration::from_secs(30)), parse_duration("30"));
+        assert_eq!(Ok(Duration::from_secs(300)), parse_duration("5m"));
+        assert_eq!(Ok(Duration::from_secs(7200)), parse_duration("2h"));
+        assert!(parse_duration("m").is_err());
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn trimmed() {
+        let trimmed = |value: &str| round_trimmed(&BigDecimal::from_str(value).unwrap(), 2);
+        assert_eq!("4800", trimmed("4800.00").to_string());
+        assert_eq!("0.1", trimmed("0.104").to_string());
+        assert_eq!("-25", trimmed("-25.001").to_string());
+    }
 }
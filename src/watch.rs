@@ -0,0 +1,376 @@
+use crate::connector::{ApiError, Connector, ConnectorError};
+use crate::currency::CurrencyPair;
+use crate::metrics;
+use bigdecimal::{BigDecimal, Zero};
+use error_stack::{Report, Result, ResultExt};
+use json::JsonValue;
+use std::fmt;
+use std::fmt::Formatter;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+const MAX_BACKOFF: Duration = Duration::from_hours(1);
+
+#[derive(Debug, PartialEq)]
+pub enum WatchEvent {
+    Above {
+        rate: BigDecimal,
+        threshold: BigDecimal,
+    },
+    Below {
+        rate: BigDecimal,
+        threshold: BigDecimal,
+    },
+    Moved {
+        from: BigDecimal,
+        to: BigDecimal,
+        percent: BigDecimal,
+    },
+}
+
+impl WatchEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            WatchEvent::Above { .. } => "above",
+            WatchEvent::Below { .. } => "below",
+            WatchEvent::Moved { .. } => "moved",
+        }
+    }
+    fn rate(&self) -> &BigDecimal {
+        match self {
+            WatchEvent::Above { rate, .. } | WatchEvent::Below { rate, .. } => rate,
+            WatchEvent::Moved { to, .. } => to,
+        }
+    }
+    pub fn to_json(&self, pair: &CurrencyPair) -> JsonValue {
+        let mut json = json::object! {
+            pair: pair.to_string(),
+            event: self.name(),
+            rate: self.rate().to_string(),
+        };
+        match self {
+            WatchEvent::Above { threshold, .. } | WatchEvent::Below { threshold, .. } => {
+                json["threshold"] = threshold.to_string().into();
+            }
+            WatchEvent::Moved { from, percent, .. } => {
+                json["from"] = from.to_string().into();
+                json["percent"] = percent.round(4).to_string().into();
+            }
+        }
+        json
+    }
+}
+
+impl fmt::Display for WatchEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchEvent::Above { rate, threshold } => {
+                write!(f, "rate {rate} crossed above {threshold}")
+            }
+            WatchEvent::Below { rate, threshold } => {
+                write!(f, "rate {rate} crossed below {threshold}")
+            }
+            WatchEvent::Moved { from, to, percent } => {
+                write!(f, "rate moved from {from} to {to} ({}%)", percent.round(4))
+            }
+        }
+    }
+}
+
+/// Keeps the state needed to detect threshold crossings between polls
+pub struct Watcher {
+    above: Option<BigDecimal>,
+    below: Option<BigDecimal>,
+    change: Option<BigDecimal>,
+    last_rate: Option<BigDecimal>,
+    reference_rate: Option<BigDecimal>,
+}
+
+impl Watcher {
+    pub fn new(
+        above: Option<BigDecimal>,
+        below: Option<BigDecimal>,
+        change: Option<BigDecimal>,
+    ) -> Self {
+        Watcher {
+            above,
+            below,
+            change,
+            last_rate: None,
+            reference_rate: None,
+        }
+    }
+
+    pub fn observe(&mut self, rate: &BigDecimal) -> Vec<WatchEvent> {
+        let mut events = Vec::new();
+        if let Some(above) = &self.above {
+            if rate > above && self.last_rate.as_ref().is_none_or(|last| last <= above) {
+                events.push(WatchEvent::Above {
+                    rate: rate.clone(),
+                    threshold: above.clone(),
+                });
+            }
+        }
+        if let Some(below) = &self.below {
+            if rate < below && self.last_rate.as_ref().is_none_or(|last| last >= below) {
+                events.push(WatchEvent::Below {
+                    rate: rate.clone(),
+                    threshold: below.clone(),
+                });
+            }
+        }
+        match (&self.change, &self.reference_rate) {
+            (Some(change), Some(reference)) if !reference.is_zero() => {
+                let percent = (rate - reference) * BigDecimal::from(100) / reference;
+                if percent.abs() > *change {
+                    events.push(WatchEvent::Moved {
+                        from: reference.clone(),
+                        to: rate.clone(),
+                        percent,
+                    });
+                    self.reference_rate = Some(rate.clone());
+                }
+            }
+            _ => self.reference_rate = Some(rate.clone()),
+        }
+        self.last_rate = Some(rate.clone());
+        events
+    }
+}
+
+/// Where events are delivered besides standard output
+pub struct Notifier {
+    exec: Option<String>,
+    webhook: Option<String>,
+    #[cfg(feature = "webhook")]
+    http_client: reqwest::blocking::Client,
+}
+
+impl Notifier {
+    pub fn new(exec: Option<String>, webhook: Option<String>) -> Self {
+        Notifier {
+            exec,
+            webhook,
+            #[cfg(feature = "webhook")]
+            http_client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    pub fn notify(&self, pair: &CurrencyPair, event: &WatchEvent) {
+        println!("{pair}: {event}");
+        let payload = event.to_json(pair);
+        if let Some(exec) = &self.exec {
+            if let Err(err) = run_command(exec, &payload) {
+                tracing::error!("\n{err:?}");
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            if let Err(err) = self.post_webhook(webhook, &payload) {
+                tracing::error!("\n{err:?}");
+            }
+        }
+    }
+
+    #[cfg(feature = "webhook")]
+    fn post_webhook(&self, url: &str, payload: &JsonValue) -> Result<(), ConnectorError> {
+        self.http_client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.dump())
+            .send()
+            .and_then(reqwest::blocking::Response::error_for_status)
+            .change_context(ConnectorError::SendingError)
+            .attach_printable_lazy(|| format!("Error with posting event to {url}"))?;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "webhook"))]
+    #[allow(clippy::unused_self)]
+    fn post_webhook(&self, url: &str, _payload: &JsonValue) -> Result<(), ConnectorError> {
+        let msg = format!("Posting event to {url} requires webhook cargo feature");
+        Err(Report::new(ConnectorError::SendingError).attach_printable(msg))
+    }
+}
+
+/// Run `exec` through the shell with the event passed in `EAPP_EVENT*` variables
+fn run_command(exec: &str, payload: &JsonValue) -> Result<(), ConnectorError> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let status = Command::new(shell)
+        .arg(flag)
+        .arg(exec)
+        .env("EAPP_EVENT", payload.dump())
+        .env("EAPP_EVENT_TYPE", payload["event"].to_string())
+        .env("EAPP_EVENT_PAIR", payload["pair"].to_string())
+        .env("EAPP_EVENT_RATE", payload["rate"].to_string())
+        .status()
+        .change_context(ConnectorError::SendingError)
+        .attach_printable_lazy(|| format!("Error with running {exec}"))?;
+    if status.success() {
+        Ok(())
+    } else {
+        let msg = format!("Command {exec} exited with {status}");
+        Err(Report::new(ConnectorError::SendingError).attach_printable(msg))
+    }
+}
+
+fn is_too_many_requests(err: &Report<ConnectorError>) -> bool {
+    matches!(err.current_context(), ConnectorError::RateLimited)
+        || matches!(
+            err.downcast_ref::<ApiError>(),
+            Some(ApiError::TooManyRequests)
+        )
+}
+
+/// Latest exchange rate of `pair`
+pub fn current_rate(
+    connector: &dyn Connector,
+    pair: &CurrencyPair,
+) -> Result<BigDecimal, ConnectorError> {
+    connector
+        .latest(pair.get_base(), Some(vec![pair.get_target().to_string()]))?
+        .into_iter()
+        .find(|latest| latest.get_currency().get_short_code() == pair.get_target())
+        .map(|latest| latest.get_rate().clone())
+        .ok_or_else(|| {
+            let msg = format!("No exchange rate for {pair}");
+            Report::new(ConnectorError::InvalidInput(msg.clone())).attach_printable(msg)
+        })
+}
+
+/// Poll the connector every `interval` until an error other than too many requests occurs
+pub fn watch(
+    connector: &dyn Connector,
+    pair: &CurrencyPair,
+    interval: Duration,
+    mut watcher: Watcher,
+    notifier: &Notifier,
+) -> Result<(), ConnectorError> {
+    let mut backoff = interval;
+    let mut retries = 0_u32;
+    loop {
+        match current_rate(connector, pair) {
+            Ok(rate) => {
+                tracing::info!(retries, "{pair}: {rate}");
+                metrics::set_watch_rate(&pair.to_string(), &rate);
+                backoff = interval;
+                retries = 0;
+                for event in watcher.observe(&rate) {
+                    notifier.notify(pair, &event);
+                }
+            }
+            Err(err) if is_too_many_requests(&err) => {
+                backoff = backoff.saturating_mul(2).min(MAX_BACKOFF.max(interval));
+                retries += 1;
+                tracing::warn!(
+                    retries,
+                    "Too many requests, next try in {}s",
+                    backoff.as_secs()
+                );
+            }
+            Err(err) => return Err(err),
+        }
+        thread::sleep(backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> BigDecimal {
+        BigDecimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn crossing_thresholds() {
+        let mut watcher = Watcher::new(Some(decimal("4.1")), Some(decimal("3.9")), None);
+        assert!(watcher.observe(&decimal("4")).is_empty());
+        assert_eq!(
+            vec![WatchEvent::Above {
+                rate: decimal("4.2"),
+                threshold: decimal("4.1")
+            }],
+            watcher.observe(&decimal("4.2"))
+        );
+        assert!(watcher.observe(&decimal("4.3")).is_empty());
+        assert_eq!(
+            vec![WatchEvent::Below {
+                rate: decimal("3.8"),
+                threshold: decimal("3.9")
+            }],
+            watcher.observe(&decimal("3.8"))
+        );
+    }
+
+    #[test]
+    fn moving_by_percent() {
+        let mut watcher = Watcher::new(None, None, Some(decimal("1")));
+        assert!(watcher.observe(&decimal("4")).is_empty());
+        assert!(watcher.observe(&decimal("4.02")).is_empty());
+        assert_eq!(
+            vec![WatchEvent::Moved {
+                from: decimal("4"),
+                to: decimal("4.05"),
+                percent: decimal("1.25")
+            }],
+            watcher.observe(&decimal("4.05"))
+        );
+        assert!(watcher.observe(&decimal("4.06")).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "webhook")]
+    fn post_to_webhook() {
+        use std::io::{BufRead, BufReader, Read, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            (request_line, String::from_utf8(body).unwrap())
+        });
+
+        let pair = CurrencyPair::from_str("USD/PLN").unwrap();
+        let event = WatchEvent::Above {
+            rate: decimal("4.2"),
+            threshold: decimal("4.1"),
+        };
+        Notifier::new(None, None)
+            .post_webhook(&url, &event.to_json(&pair))
+            .unwrap();
+        let (request_line, body) = server.join().unwrap();
+        assert!(request_line.starts_with("POST /hook"));
+        assert_eq!(
+            json::object! {pair: "USD/PLN", event: "above", rate: "4.2", threshold: "4.1"},
+            json::parse(&body).unwrap()
+        );
+    }
+}
@@ -0,0 +1,492 @@
+use crate::connector::{error_message, Connector, ConnectorError};
+use crate::currency::CurrencyPair;
+use crate::redact::redact;
+use crate::server::{bind, runtime, serve_with, ConnectorHandle};
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, SecondsFormat, Utc};
+use error_stack::{Result, ResultExt};
+use futures::{SinkExt, StreamExt};
+use json::JsonValue;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::TcpListener;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, Notify, OnceCell};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::WebSocketStream;
+use tracing::Instrument;
+
+/// Events kept for connections which are slow to read them
+const EVENTS_CAPACITY: usize = 256;
+
+/// Rate of a pair as of a poll
+#[derive(Clone, Debug)]
+struct Update {
+    pair: CurrencyPair,
+    rate: BigDecimal,
+    time: DateTime<Utc>,
+}
+
+impl Update {
+    fn to_json(&self) -> String {
+        json::object! {
+            pair: self.pair.to_string(),
+            rate: self.rate.to_string(),
+            time: self.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+        .dump()
+    }
+}
+
+/// Event sent by the poller to every connection
+#[derive(Clone, Debug)]
+enum Event {
+    Rate(Update),
+    /// Poll of the pairs failed
+    Error {
+        pairs: Vec<CurrencyPair>,
+        message: String,
+    },
+}
+
+fn error_json(message: &str, pair: Option<&str>) -> String {
+    let mut json = json::object! { error: message };
+    if let Some(pair) = pair {
+        json["pair"] = pair.into();
+    }
+    json.dump()
+}
+
+/// Pairs subscribed by connections and their most recent rates
+#[derive(Default)]
+struct State {
+    subscribers: HashMap<CurrencyPair, usize>,
+    rates: HashMap<CurrencyPair, Update>,
+}
+
+/// Subscriptions shared by connections and the poller
+struct Hub {
+    connector: ConnectorHandle,
+    state: Mutex<State>,
+    events: broadcast::Sender<Event>,
+    /// Wakes the poller when a pair without a known rate is subscribed
+    subscribed: Notify,
+    currencies: OnceCell<HashSet<String>>,
+}
+
+impl Hub {
+    fn new(connector: ConnectorHandle) -> Self {
+        Hub {
+            connector,
+            state: Mutex::default(),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+            subscribed: Notify::new(),
+            currencies: OnceCell::new(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Count a subscriber of `pair`, returns its most recent rate
+    fn subscribe(&self, pair: &CurrencyPair) -> Option<Update> {
+        let mut state = self.state();
+        *state.subscribers.entry(pair.clone()).or_default() += 1;
+        let update = state.rates.get(pair).cloned();
+        if update.is_none() {
+            self.subscribed.notify_one();
+        }
+        update
+    }
+
+    /// Forget `pair` with its last subscriber
+    fn unsubscribe(&self, pair: &CurrencyPair) {
+        let mut state = self.state();
+        if let Some(count) = state.subscribers.get_mut(pair) {
+            *count -= 1;
+            if *count == 0 {
+                state.subscribers.remove(pair);
+                state.rates.remove(pair);
+            }
+        }
+    }
+
+    /// Error message when a currency of `pair` is unknown to the connector. Currencies are
+    /// listed once, pairs are not checked when listing fails.
+    async fn check(&self, pair: &CurrencyPair) -> Option<String> {
+        let currencies = self
+            .currencies
+            .get_or_try_init(|| async {
+                let currencies = self
+                    .connector
+                    .call(|connector| connector.list_currencies())
+                    .await?;
+                Ok::<_, error_stack::Report<ConnectorError>>(
+                    currencies
+                        .iter()
+                        .map(|currency| currency.get_short_code().to_string())
+                        .collect(),
+                )
+            })
+            .await;
+        match currencies {
+            Ok(currencies) => [pair.get_base(), pair.get_target()]
+                .into_iter()
+                .find(|code| !currencies.contains(*code))
+                .map(|code| format!("Unknown currency {code}")),
+            Err(err) => {
+                tracing::warn!(
+                    "Error with listing currencies: {}",
+                    redact(&error_message(&err))
+                );
+                None
+            }
+        }
+    }
+
+    /// Poll subscribed pairs with one request per base, only pairs without a known rate
+    /// when `new`, and send rates which changed
+    async fn poll(&self, new: bool) {
+        let mut bases = BTreeMap::<String, Vec<CurrencyPair>>::new();
+        {
+            let state = self.state();
+            for pair in state.subscribers.keys() {
+                if !new || !state.rates.contains_key(pair) {
+                    bases
+                        .entry(pair.get_base().to_string())
+                        .or_default()
+                        .push(pair.clone());
+                }
+            }
+        }
+        for (base, pairs) in bases {
+            let targets = pairs
+                .iter()
+                .map(|pair| pair.get_target().to_string())
+                .collect::<Vec<_>>();
+            let polled = base.clone();
+            let result = self
+                .connector
+                .call(move |connector| connector.latest(&polled, Some(targets)))
+                .await;
+            let time = Utc::now();
+            let rates = match result {
+                Ok(rates) => rates,
+                Err(err) => {
+                    let message = redact(&error_message(&err));
+                    tracing::warn!("Polling {base}: {message}");
+                    let _ = self.events.send(Event::Error { pairs, message });
+                    continue;
+                }
+            };
+            let mut state = self.state();
+            for latest in rates {
+                let code = latest.get_currency().get_short_code();
+                let Some(pair) = pairs.iter().find(|pair| pair.get_target() == code) else {
+                    continue;
+                };
+                if !state.subscribers.contains_key(pair)
+                    || state
+                        .rates
+                        .get(pair)
+                        .is_some_and(|update| &update.rate == latest.get_rate())
+                {
+                    continue;
+                }
+                let update = Update {
+                    pair: pair.clone(),
+                    rate: latest.get_rate().clone(),
+                    time,
+                };
+                state.rates.insert(pair.clone(), update.clone());
+                let _ = self.events.send(Event::Rate(update));
+            }
+        }
+    }
+
+    /// Poll every `interval` and right after a new pair is subscribed
+    async fn run_poller(self: Arc<Self>, interval: Duration) {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => self.poll(false).await,
+                () = self.subscribed.notified() => self.poll(true).await,
+            }
+        }
+    }
+
+    /// Replies to a message of a client subscribing to or unsubscribing from pairs in
+    /// `connection`
+    async fn handle(&self, text: &str, connection: &mut HashSet<CurrencyPair>) -> Vec<String> {
+        let Ok(request) = json::parse(text) else {
+            return vec![error_json("Message is not json", None)];
+        };
+        let (pairs, subscribe) = match (&request["subscribe"], &request["unsubscribe"]) {
+            (JsonValue::Array(pairs), JsonValue::Null) => (pairs, true),
+            (JsonValue::Null, JsonValue::Array(pairs)) => (pairs, false),
+            _ => {
+                return vec![error_json(
+                    "Expected {\"subscribe\": [pairs]} or {\"unsubscribe\": [pairs]}",
+                    None,
+                )]
+            }
+        };
+        let mut replies = Vec::new();
+        for value in pairs {
+            let text = value.as_str().unwrap_or_default();
+            let pair = match CurrencyPair::from_str(text) {
+                Ok(pair) => pair,
+                Err(err) => {
+                    replies.push(error_json(&err, Some(text)));
+                    continue;
+                }
+            };
+            if !subscribe {
+                if connection.remove(&pair) {
+                    self.unsubscribe(&pair);
+                }
+            } else if let Some(err) = self.check(&pair).await {
+                replies.push(error_json(&err, Some(&pair.to_string())));
+            } else if connection.insert(pair.clone()) {
+                if let Some(update) = self.subscribe(&pair) {
+                    replies.push(update.to_json());
+                }
+            }
+        }
+        replies
+    }
+}
+
+/// Exchange messages with a client until it closes the connection
+async fn forward(
+    hub: &Hub,
+    socket: &mut WebSocketStream<TcpStream>,
+    pairs: &mut HashSet<CurrencyPair>,
+) -> std::result::Result<(), tungstenite::Error> {
+    let mut events = hub.events.subscribe();
+    loop {
+        tokio::select! {
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    for reply in hub.handle(&text, pairs).await {
+                        socket.send(Message::text(reply)).await?;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | None => return Ok(()),
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(err),
+            },
+            event = events.recv() => match event {
+                Ok(Event::Rate(update)) if pairs.contains(&update.pair) => {
+                    socket.send(Message::text(update.to_json())).await?;
+                }
+                Ok(Event::Error { pairs: failed, message }) => {
+                    for pair in failed.iter().filter(|pair| pairs.contains(*pair)) {
+                        let reply = error_json(&message, Some(&pair.to_string()));
+                        socket.send(Message::text(reply)).await?;
+                    }
+                }
+                Ok(Event::Rate(_)) => {}
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Connection skipped {skipped} rates");
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            },
+        }
+    }
+}
+
+async fn connection(
+    hub: Arc<Hub>,
+    stream: TcpStream,
+) -> std::result::Result<(), tungstenite::Error> {
+    let mut socket = tokio_tungstenite::accept_async(stream).await?;
+    let mut pairs = HashSet::new();
+    let result = forward(&hub, &mut socket, &mut pairs).await;
+    for pair in &pairs {
+        hub.unsubscribe(pair);
+    }
+    result
+}
+
+/// Serve rates of subscribed pairs over WebSocket on `addr`, polling the connector every
+/// `interval`. Connector calls run on the calling thread.
+pub fn serve(
+    connector: &dyn Connector,
+    addr: &str,
+    interval: Duration,
+) -> Result<(), ConnectorError> {
+    serve_listener(connector, bind(addr)?, interval)
+}
+
+/// Serve rates over WebSocket on nonblocking `listener`, see [`serve`]
+pub fn serve_listener(
+    connector: &dyn Connector,
+    listener: TcpListener,
+    interval: Duration,
+) -> Result<(), ConnectorError> {
+    let addr = listener
+        .local_addr()
+        .change_context(ConnectorError::SendingError)?;
+    serve_with(connector, move |connector| {
+        runtime()?.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener)
+                .change_context(ConnectorError::SendingError)?;
+            let hub = Arc::new(Hub::new(connector));
+            tokio::spawn(hub.clone().run_poller(interval));
+            tracing::info!("Serving WebSocket on ws://{addr}");
+            loop {
+                let (stream, peer) = match listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        tracing::warn!("Error with accepting connection: {err}");
+                        continue;
+                    }
+                };
+                let span = tracing::info_span!("websocket", %peer);
+                let hub = hub.clone();
+                tokio::spawn(
+                    async move {
+                        if let Err(err) = connection(hub, stream).await {
+                            tracing::debug!("Connection closed: {err}");
+                        }
+                    }
+                    .instrument(span),
+                );
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connector_output::{ExchangeOutput, LatestOutput};
+    use crate::connectors::mock_connector::MockConnector;
+    use crate::currency::Currency;
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use tokio_tungstenite::connect_async;
+
+    type Client = WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>;
+
+    /// Mock connector counting calls of `latest`
+    struct CountingConnector {
+        connector: MockConnector,
+        latest: Arc<AtomicUsize>,
+    }
+
+    impl Connector for CountingConnector {
+        fn exchange(
+            &self,
+            source: &str,
+            target: &str,
+            amount: &BigDecimal,
+        ) -> Result<ExchangeOutput, ConnectorError> {
+            self.connector.exchange(source, target, amount)
+        }
+        fn list_currencies(&self) -> Result<Vec<Currency>, ConnectorError> {
+            self.connector.list_currencies()
+        }
+        fn latest(
+            &self,
+            base: &str,
+            target: Option<Vec<String>>,
+        ) -> Result<Vec<LatestOutput>, ConnectorError> {
+            self.latest.fetch_add(1, Ordering::SeqCst);
+            self.connector.latest(base, target)
+        }
+        fn historical(
+            &self,
+            date: NaiveDate,
+            base: &str,
+            target: Option<Vec<String>>,
+        ) -> Result<Vec<LatestOutput>, ConnectorError> {
+            self.connector.historical(date, base, target)
+        }
+    }
+
+    fn start_server(connector: MockConnector, interval: Duration) -> (String, Arc<AtomicUsize>) {
+        let listener = bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let latest = Arc::new(AtomicUsize::new(0));
+        let connector = CountingConnector {
+            connector,
+            latest: latest.clone(),
+        };
+        thread::spawn(move || serve_listener(&connector, listener, interval));
+        (format!("ws://{addr}"), latest)
+    }
+
+    async fn send(client: &mut Client, text: &str) {
+        client.send(Message::text(text)).await.unwrap();
+    }
+
+    async fn receive(client: &mut Client) -> JsonValue {
+        let message = tokio::time::timeout(Duration::from_secs(5), client.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        json::parse(message.to_text().unwrap()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn subscribe_to_rates() {
+        let (url, latest) = start_server(MockConnector::new(), Duration::from_secs(1));
+        let (mut client, _) = connect_async(&url).await.unwrap();
+        send(
+            &mut client,
+            r#"{"subscribe": ["usd/pln", "USD/XYZ", "USDPLN"]}"#,
+        )
+        .await;
+        assert_eq!("USD/XYZ", receive(&mut client).await["pair"]);
+        let invalid = receive(&mut client).await;
+        assert_eq!("USDPLN", invalid["pair"]);
+        assert!(invalid["error"].is_string());
+        let update = receive(&mut client).await;
+        assert_eq!("USD/PLN", update["pair"]);
+        assert_eq!("4", update["rate"]);
+        assert!(DateTime::parse_from_rfc3339(update["time"].as_str().unwrap()).is_ok());
+
+        let (mut other, _) = connect_async(&url).await.unwrap();
+        send(&mut other, r#"{"subscribe": ["USD/PLN", "USD/GBP"]}"#).await;
+        assert_eq!(update, receive(&mut other).await);
+        assert_eq!("USD/GBP", receive(&mut other).await["pair"]);
+
+        // Fixed rates do not change, so nothing more is pushed, and both pairs of USD are
+        // polled with one request per interval
+        let before = latest.load(Ordering::SeqCst);
+        let next = tokio::time::timeout(Duration::from_millis(2500), client.next()).await;
+        assert!(next.is_err());
+        assert!(latest.load(Ordering::SeqCst) - before <= 3);
+    }
+
+    #[tokio::test]
+    async fn push_changed_rates() {
+        let connector = MockConnector::builder()
+            .currency("USD", None, BigDecimal::from(1))
+            .currency("PLN", None, BigDecimal::from(4))
+            .jitter(BigDecimal::from_str("0.1").unwrap())
+            .build();
+        let (url, _) = start_server(connector, Duration::from_millis(100));
+        let (mut client, _) = connect_async(&url).await.unwrap();
+        send(&mut client, r#"{"subscribe": ["USD/PLN"]}"#).await;
+        let first = receive(&mut client).await;
+        let second = receive(&mut client).await;
+        assert_eq!("USD/PLN", second["pair"]);
+        assert_ne!(first["rate"], second["rate"]);
+
+        send(&mut client, r#"{"unsubscribe": ["USD/PLN"]}"#).await;
+        send(&mut client, r#"{"watch": "USD/PLN"}"#).await;
+        let mut reply = receive(&mut client).await;
+        while reply["error"].is_null() {
+            reply = receive(&mut client).await;
+        }
+        assert!(reply["pair"].is_null());
+    }
+}